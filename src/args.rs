@@ -0,0 +1,661 @@
+use std::collections::HashMap;
+use crate::command::bold_text;
+
+
+const HELP_WIDTH: usize = 100;
+
+
+/// A declarative description of a command's arguments and flags, used to parse its command line
+/// and to generate its usage and help messages.
+#[derive(PartialEq, Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub args: &'static [ArgSpec],
+    pub flags: &'static [FlagSpec],
+    /// If not empty, the first argument may name one of these subcommands, which are parsed with
+    /// their own arguments and flags. Subcommands can't have subcommands of their own.
+    pub subcommands: &'static [CommandSpec],
+}
+
+/// A positional argument.
+#[derive(PartialEq, Debug)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub kind: ValueKind,
+}
+
+impl ArgSpec {
+
+    /// Set what the argument's values are, for completion.
+    pub const fn of_kind(mut self, kind: ValueKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+}
+
+/// How many values a positional argument takes. Only the last argument may be optional or
+/// repeated.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Arity {
+    Required,
+    Optional,
+    /// Any number of values, but at least `min`.
+    Repeated { min: usize },
+}
+
+/// What an argument's or flag's values are, so that they can be completed.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum ValueKind {
+    /// Anything. If the value's name lists choices ("dot|mermaid"), it's one of those.
+    Text,
+    /// The name of an action.
+    Action,
+    /// The name of a project.
+    Project,
+    /// The ID of a task in the active project.
+    Task,
+    /// The ID of a task in the active project, or of a task in another project (project:task_id).
+    Dependency,
+    /// A status in the active project's workflow, or one of the given extra values.
+    Status(&'static [&'static str]),
+}
+
+/// A named flag, e.g. "--cascade" or "--depth N".
+#[derive(PartialEq, Debug)]
+pub struct FlagSpec {
+    pub name: &'static str,
+    /// The name of the flag's value, if it takes one.
+    pub value: Option<&'static str>,
+    pub required: bool,
+    pub help: &'static str,
+    /// What the flag's value is, if it takes one.
+    pub kind: ValueKind,
+}
+
+impl FlagSpec {
+
+    /// Set what the flag's value is, for completion.
+    pub const fn of_kind(mut self, kind: ValueKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+}
+
+
+/// A command line parsed according to a `CommandSpec`.
+#[derive(PartialEq, Debug)]
+pub struct ParsedArgs {
+    pub subcommand: Option<&'static str>,
+    pub positional: Vec<String>,
+    /// Flags which were given, mapped to their values. Flags without a value map to "".
+    pub flags: HashMap<String, String>,
+}
+
+
+/// Parse a command's arguments (not including the command's name). Flags may come anywhere, and
+/// their values can be given as "--flag value" or "--flag=value". Everything after "--" is
+/// positional. Unknown flags and unexpected arguments are rejected.
+pub fn parse(spec: &'static CommandSpec, args: &[String]) -> Result<ParsedArgs, String> {
+    if spec.subcommands.is_empty() {
+        return parse_flat(spec, args, &usage(spec, None));
+    }
+    match args.first() {
+        Some(first) if !first.starts_with('-') => {
+            let subcommand = find_subcommand(spec, first)?;
+            let mut parsed = parse_flat(subcommand, &args[1..], &usage(spec, Some(subcommand)))?;
+            parsed.subcommand = Some(subcommand.name);
+            Ok(parsed)
+        },
+        _ => parse_flat(spec, args, &usage(spec, None)),
+    }
+}
+
+/// Find the subcommand with the given name.
+pub fn find_subcommand(
+    spec: &'static CommandSpec,
+    name: &str,
+) -> Result<&'static CommandSpec, String> {
+    match spec.subcommands.iter().find(|subcommand| subcommand.name == name) {
+        Some(subcommand) => Ok(subcommand),
+        None => {
+            let names: Vec<&str> = spec.subcommands.iter().map(|sub| sub.name).collect();
+            Err(format!(
+                "no subcommand \"{}\" for {}{}",
+                name,
+                spec.name,
+                did_you_mean(name, &names),
+            ))
+        },
+    }
+}
+
+fn parse_flat(spec: &CommandSpec, args: &[String], usage: &str) -> Result<ParsedArgs, String> {
+    let mut positional = Vec::new();
+    let mut flags = HashMap::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == "--" {
+            positional.extend(args_iter.by_ref().cloned());
+            break;
+        }
+        if !arg.starts_with("--") {
+            positional.push(arg.clone());
+            continue;
+        }
+
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (&arg[..], None),
+        };
+        let flag = match spec.flags.iter().find(|flag| flag.name == name) {
+            Some(flag) => flag,
+            None => {
+                let names: Vec<&str> = spec.flags.iter().map(|flag| flag.name).collect();
+                return Err(format!(
+                    "unknown flag \"{}\"{}\n{}",
+                    name,
+                    did_you_mean(name, &names),
+                    usage,
+                ));
+            },
+        };
+        let value = match (flag.value, inline_value) {
+            (None, None) => String::new(),
+            (None, Some(_)) => {
+                return Err(format!("flag {} does not take a value\n{}", flag.name, usage));
+            },
+            (Some(_), Some(value)) => value,
+            (Some(value_name), None) => match args_iter.next() {
+                Some(value) => value.clone(),
+                None => return Err(format!(
+                    "flag {} needs a value {}\n{}",
+                    flag.name,
+                    value_name,
+                    usage,
+                )),
+            },
+        };
+        flags.insert(flag.name.to_string(), value);
+    }
+
+    for flag in spec.flags.iter().filter(|flag| flag.required) {
+        if !flags.contains_key(flag.name) {
+            return Err(format!("missing flag {}\n{}", flag.name, usage));
+        }
+    }
+    check_arg_count(spec, &positional, usage)?;
+    Ok(ParsedArgs { subcommand: None, positional, flags })
+}
+
+/// Check that the number of positional arguments matches the spec.
+fn check_arg_count(spec: &CommandSpec, positional: &[String], usage: &str) -> Result<(), String> {
+    let mut min = 0;
+    let mut max = Some(0);
+    for arg in spec.args {
+        match arg.arity {
+            Arity::Required => {
+                min += 1;
+                max = max.map(|max| max + 1);
+            },
+            Arity::Optional => max = max.map(|max| max + 1),
+            Arity::Repeated { min: repeated_min } => {
+                min += repeated_min;
+                max = None;
+            },
+        }
+    }
+
+    if positional.len() < min {
+        let missing = spec.args.iter()
+            .scan(0, |seen, arg| {
+                *seen += match arg.arity {
+                    Arity::Required => 1,
+                    Arity::Optional => 0,
+                    Arity::Repeated { min } => min,
+                };
+                Some((arg, *seen))
+            })
+            .find(|(_, seen)| *seen > positional.len())
+            .map(|(arg, _)| arg.name)
+            .unwrap_or("argument");
+        return Err(format!("missing {}\n{}", missing, usage));
+    }
+    if let Some(max) = max {
+        if positional.len() > max {
+            return Err(unexpected_arg_msg(&positional[max], usage));
+        }
+    }
+    Ok(())
+}
+
+fn unexpected_arg_msg(arg: &str, usage: &str) -> String {
+    format!("unexpected argument \"{}\"\n{}", arg, usage)
+}
+
+/// Get the usage message for a command, or one of its subcommands.
+pub fn usage(spec: &CommandSpec, subcommand: Option<&CommandSpec>) -> String {
+    let mut parts = vec!["usage: tasktree".to_string(), spec.name.to_string()];
+    let spec = match subcommand {
+        Some(subcommand) => {
+            parts.push(subcommand.name.to_string());
+            subcommand
+        },
+        None => {
+            if !spec.subcommands.is_empty() {
+                let names: Vec<&str> = spec.subcommands.iter().map(|sub| sub.name).collect();
+                parts.push(format!("[{}] ...", names.join("|")));
+            }
+            spec
+        },
+    };
+    for arg in spec.args {
+        parts.push(match arg.arity {
+            Arity::Required => arg.name.to_string(),
+            Arity::Optional => format!("[{}]", arg.name),
+            Arity::Repeated { min: 0 } => format!("[{}...]", arg.name),
+            Arity::Repeated { .. } => format!("{}...", arg.name),
+        });
+    }
+    for flag in spec.flags {
+        let flag_usage = match flag.value {
+            Some(value) => format!("{} {}", flag.name, value),
+            None => flag.name.to_string(),
+        };
+        parts.push(match flag.required {
+            true => flag_usage,
+            false => format!("[{}]", flag_usage),
+        });
+    }
+    parts.join(" ")
+}
+
+/// Get the help message for a command, or one of its subcommands: its usage, summary, flags and
+/// subcommands.
+pub fn help(spec: &CommandSpec, subcommand: Option<&CommandSpec>) -> String {
+    let mut lines = vec![usage(spec, subcommand)];
+    let shown = subcommand.unwrap_or(spec);
+    lines.push(String::new());
+    lines.push(wrap("", shown.summary, HELP_WIDTH));
+
+    if !shown.flags.is_empty() {
+        lines.push(String::new());
+        lines.push(bold_text("flags:"));
+        for flag in shown.flags {
+            let flag_usage = match flag.value {
+                Some(value) => format!("{} {}", flag.name, value),
+                None => flag.name.to_string(),
+            };
+            lines.push(wrap(&format!("  {:<21} ", flag_usage), flag.help, HELP_WIDTH));
+        }
+    }
+    if subcommand.is_none() && !spec.subcommands.is_empty() {
+        lines.push(String::new());
+        lines.push(bold_text("subcommands:"));
+        for subcommand in spec.subcommands {
+            let prefix = format!("  {:<21} ", subcommand.name);
+            lines.push(wrap(&prefix, subcommand.summary, HELP_WIDTH));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Wrap text to the given width after a prefix, indenting the following lines to line up with the
+/// first.
+pub fn wrap(prefix: &str, text: &str, width: usize) -> String {
+    let indent = " ".repeat(prefix.chars().count());
+    let mut lines = Vec::new();
+    let mut line = prefix.to_string();
+    let mut line_empty = true;
+    for word in text.split_whitespace() {
+        if !line_empty && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(line);
+            line = indent.clone();
+            line_empty = true;
+        }
+        if !line_empty {
+            line.push(' ');
+        }
+        line.push_str(word);
+        line_empty = false;
+    }
+    lines.push(line);
+    lines.join("\n")
+}
+
+/// Suggest the closest of the candidates to a mistyped name, as " (did you mean ...?)". Returns an
+/// empty string if none of them is close.
+pub fn did_you_mean(name: &str, candidates: &[&str]) -> String {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let closest = candidates.iter()
+        .map(|candidate| (edit_distance(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min();
+    match closest {
+        Some((_, candidate)) => format!(" (did you mean \"{}\"?)", candidate),
+        None => String::new(),
+    }
+}
+
+/// Split a line into words at whitespace. Single or double quotes keep whitespace in a word, and
+/// a backslash escapes the next character (except within single quotes).
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some('\''), _) => word.push(c),
+            (_, '\\') => match chars.next() {
+                Some(escaped) => {
+                    word.push(escaped);
+                    in_word = true;
+                },
+                None => return Err("Unfinished escape at the end of the line.".to_string()),
+            },
+            (Some(_), _) => word.push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                in_word = true;
+            },
+            (None, _) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            },
+            (None, _) => {
+                word.push(c);
+                in_word = true;
+            },
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("Missing closing quote ({}).", q));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Expand an alias's command line for the given arguments: "{1}", "{2}", ... are replaced by the
+/// positional arguments, and the arguments which no placeholder uses (including flags) are added
+/// at the end. Which arguments are flag values is looked up in the spec of the command which the
+/// alias runs, among `specs`.
+pub fn expand_alias(
+    name: &str,
+    command_line: &str,
+    args: &[String],
+    specs: &[CommandSpec],
+) -> Result<Vec<String>, String> {
+    let words = split_words(command_line)
+        .map_err(|msg| format!("Invalid alias {} ({}): {}", name, command_line, msg))?;
+    let positional_idxs = find_positional(get_alias_flags(&words, specs), args);
+    let positional: Vec<&String> = positional_idxs.iter().map(|idx| &args[*idx]).collect();
+    let mut used = vec![false; positional.len()];
+    let mut expanded = Vec::new();
+    for word in words {
+        let mut result = String::new();
+        let mut rest = &word[..];
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let number_len = after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len());
+            let number = match after[number_len..].starts_with('}') {
+                true => after[..number_len].parse::<usize>().ok().filter(|number| *number > 0),
+                false => None,
+            };
+            let number = match number {
+                Some(number) => number,
+                None => {
+                    result.push('{');
+                    rest = after;
+                    continue;
+                },
+            };
+            match positional.get(number - 1) {
+                Some(arg) => result.push_str(arg),
+                None => return Err(format!(
+                    "Missing argument {{{}}} for alias {} ({}).",
+                    number,
+                    name,
+                    command_line,
+                )),
+            }
+            used[number - 1] = true;
+            rest = &after[number_len + 1..];
+        }
+        result.push_str(rest);
+        expanded.push(result);
+    }
+
+    for (idx, arg) in args.iter().enumerate() {
+        match positional_idxs.iter().position(|positional_idx| *positional_idx == idx) {
+            Some(number) if used[number] => (),
+            _ => expanded.push(arg.clone()),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Get the flags of the command (or subcommand) which an alias's words run.
+fn get_alias_flags(words: &[String], specs: &[CommandSpec]) -> &'static [FlagSpec] {
+    let spec = match words.first().and_then(|name| specs.iter().find(|spec| spec.name == name)) {
+        Some(spec) => spec,
+        None => return &[],
+    };
+    let subcommand = words.get(1)
+        .and_then(|name| spec.subcommands.iter().find(|subcommand| subcommand.name == name));
+    match subcommand {
+        Some(subcommand) => subcommand.flags,
+        None => spec.flags,
+    }
+}
+
+/// Find the indices of the positional arguments, as `parse` would: flags and their values aren't
+/// positional, and every argument after "--" is.
+fn find_positional(flags: &[FlagSpec], args: &[String]) -> Vec<usize> {
+    let mut positional = Vec::new();
+    let mut args_iter = args.iter().enumerate();
+    while let Some((idx, arg)) = args_iter.next() {
+        if arg == "--" {
+            positional.extend(args_iter.by_ref().map(|(idx, _)| idx));
+            break;
+        }
+        if !arg.starts_with("--") && arg != "-h" {
+            positional.push(idx);
+            continue;
+        }
+        let takes_value = flags.iter()
+            .any(|flag| flag.name == arg && flag.value.is_some());
+        if takes_value {
+            args_iter.next();
+        }
+    }
+    positional
+}
+
+/// The edit distance between two strings, counting insertions, deletions, substitutions and
+/// swaps of adjacent characters (the optimal string alignment distance).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i chars of a and the first j chars of b.
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_SPEC: CommandSpec = CommandSpec {
+        name: "tree",
+        summary: "Draw the tree.",
+        args: &[ArgSpec { name: "task_id", arity: Arity::Optional, kind: ValueKind::Text }],
+        flags: &[
+            FlagSpec {
+                name: "--depth",
+                value: Some("N"),
+                required: false,
+                help: "Max depth.",
+                kind: ValueKind::Text,
+            },
+            FlagSpec {
+                name: "--all",
+                value: None,
+                required: false,
+                help: "Show all.",
+                kind: ValueKind::Text,
+            },
+        ],
+        subcommands: &[],
+    };
+
+    const TEST_SUBCOMMANDS: CommandSpec = CommandSpec {
+        name: "workflow",
+        summary: "Edit the workflow.",
+        args: &[],
+        flags: &[],
+        subcommands: &[CommandSpec {
+            name: "set-transitions",
+            summary: "Set transitions.",
+            args: &[
+                ArgSpec { name: "from", arity: Arity::Required, kind: ValueKind::Text },
+                ArgSpec { name: "to", arity: Arity::Repeated { min: 0 }, kind: ValueKind::Text },
+            ],
+            flags: &[],
+            subcommands: &[],
+        }],
+    };
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_flags_and_args() {
+        static SPEC: CommandSpec = TEST_SPEC;
+        let parsed = parse(&SPEC, &to_args(&["--depth=2", "4", "--all"])).unwrap();
+        assert_eq!(parsed.positional, vec!["4".to_string()]);
+        assert_eq!(parsed.flags.get("--depth").unwrap(), "2");
+        assert_eq!(parsed.flags.get("--all").unwrap(), "");
+        let parsed = parse(&SPEC, &to_args(&["--depth", "2", "--", "--all"])).unwrap();
+        assert_eq!(parsed.positional, vec!["--all".to_string()]);
+
+        let err = parse(&SPEC, &to_args(&["4", "5"])).unwrap_err();
+        assert!(err.starts_with("unexpected argument \"5\""));
+        let err = parse(&SPEC, &to_args(&["--dept", "2"])).unwrap_err();
+        assert!(err.contains("did you mean \"--depth\"?"));
+        assert!(parse(&SPEC, &to_args(&["--depth"])).is_err());
+        assert!(parse(&SPEC, &to_args(&["--all=yes"])).is_err());
+        assert_eq!(usage(&SPEC, None), "usage: tasktree tree [task_id] [--depth N] [--all]");
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        static SPEC: CommandSpec = TEST_SUBCOMMANDS;
+        let parsed = parse(&SPEC, &to_args(&["set-transitions", "open", "closed"])).unwrap();
+        assert_eq!(parsed.subcommand, Some("set-transitions"));
+        assert_eq!(parsed.positional, to_args(&["open", "closed"]));
+        assert_eq!(parse(&SPEC, &[]).unwrap().subcommand, None);
+
+        let err = parse(&SPEC, &to_args(&["set-transition", "open"])).unwrap_err();
+        assert!(err.contains("did you mean \"set-transitions\"?"));
+        let err = parse(&SPEC, &to_args(&["set-transitions"])).unwrap_err();
+        assert!(err.starts_with("missing from\nusage: tasktree workflow set-transitions from"));
+    }
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(split_words("  set 3  closed ").unwrap(), to_args(&["set", "3", "closed"]));
+        assert_eq!(
+            split_words(r#"new "Write docs" 'for the "shell"' it\'s """#).unwrap(),
+            to_args(&["new", "Write docs", "for the \"shell\"", "it's", ""]),
+        );
+        assert!(split_words("new \"Write docs").is_err());
+        assert!(split_words("new docs\\").is_err());
+    }
+
+    static ALIAS_SPECS: &[CommandSpec] = &[
+        CommandSpec {
+            name: "move",
+            summary: "",
+            args: &[],
+            flags: &[FlagSpec {
+                name: "--to",
+                value: Some("project"),
+                required: true,
+                help: "",
+                kind: ValueKind::Project,
+            }],
+            subcommands: &[],
+        },
+    ];
+
+    #[test]
+    fn test_expand_alias() {
+        assert_eq!(
+            expand_alias("done", "set {1} closed", &to_args(&["3", "--cascade"]), &[]).unwrap(),
+            to_args(&["set", "3", "closed", "--cascade"]),
+        );
+        assert_eq!(
+            expand_alias("dep", "add-dep {2} {1}", &to_args(&["1", "2", "3"]), &[]).unwrap(),
+            to_args(&["add-dep", "2", "1", "3"]),
+        );
+        assert_eq!(
+            expand_alias("todo", "new \"TODO: {1}\" {x} {0}", &to_args(&["Write docs"]), &[])
+                .unwrap(),
+            to_args(&["new", "TODO: Write docs", "{x}", "{0}"]),
+        );
+        assert_eq!(
+            expand_alias("all", "view all", &[], &[]).unwrap(),
+            to_args(&["view", "all"]),
+        );
+        assert!(expand_alias("done", "set {1} closed", &to_args(&["--cascade"]), &[]).is_err());
+        assert_eq!(
+            expand_alias("mv", "move {1}", &to_args(&["--to", "other", "3"]), ALIAS_SPECS).unwrap(),
+            to_args(&["move", "3", "--to", "other"]),
+        );
+        assert_eq!(
+            expand_alias("mv", "move {1}", &to_args(&["--to=other", "--", "-3"]), ALIAS_SPECS)
+                .unwrap(),
+            to_args(&["move", "-3", "--to=other", "--"]),
+        );
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("veiw", "view"), 1);
+        assert_eq!(did_you_mean("veiw", &["view", "new"]), " (did you mean \"view\"?)");
+        assert_eq!(did_you_mean("nope", &["view", "new", "copy"]), "");
+        assert_eq!(wrap("  ", "aaa bbb ccc", 10), "  aaa bbb\n  ccc");
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::{ HashMap, HashSet };
+use crate::args;
+use crate::command::Command;
+use crate::session::Session;
+use crate::tree::TID;
+
+
+/// Actions which change other projects, which project is active or the configuration, or which
+/// are interactive, so they can't be part of a batch.
+const DISALLOWED_ACTIONS: &[&str] = &[
+    "new-project", "rm-project", "switch", "shell", "tui", "batch", "edit", "config",
+];
+
+
+/// A line of a batch script.
+#[derive(PartialEq, Debug)]
+enum Statement<'a> {
+    /// Run a command, optionally storing the ID of the task it creates in a variable.
+    Run { variable: Option<&'a str>, command_line: &'a str },
+    Skip,
+}
+
+
+/// Run a batch script against the active project: one command per line (without "tasktree"), as
+/// in the shell. Empty lines and lines starting with "#" are skipped. "$name = command" stores the
+/// ID of the task created by the command in a variable, and "$name" in later lines is replaced by
+/// that ID. Either every command succeeds and the project is saved once at the end, or nothing is
+/// saved.
+pub fn run(script: &str, session: &mut Session) -> Result<String, String> {
+    session.begin_transaction();
+    match run_statements(script, session) {
+        Ok(outputs) => {
+            session.commit()?;
+            let mut result = outputs.join("\n");
+            if !result.is_empty() {
+                result.push('\n');
+            }
+            result.push_str(&format!("Ran {} commands.", outputs.len()));
+            Ok(result)
+        },
+        Err(msg) => {
+            session.rollback();
+            Err(format!("{}\nNo changes were saved.", msg))
+        },
+    }
+}
+
+fn run_statements(script: &str, session: &mut Session) -> Result<Vec<String>, String> {
+    let mut variables: HashMap<&str, TID> = HashMap::new();
+    let mut outputs = Vec::new();
+    for (idx, line) in script.lines().enumerate() {
+        let line_error = |msg: String| format!("line {} ({}): {}", idx + 1, line.trim(), msg);
+        let (variable, command_line) = match parse_statement(line).map_err(line_error)? {
+            Statement::Run { variable, command_line } => (variable, command_line),
+            Statement::Skip => continue,
+        };
+        let words = args::split_words(&substitute_variables(command_line, &variables)
+            .map_err(line_error)?)
+            .map_err(line_error)?;
+        let words = Command::expand_alias(words).map_err(line_error)?;
+        if DISALLOWED_ACTIONS.contains(&&words[0][..]) {
+            return Err(line_error(format!("\"{}\" can't be used in a batch.", words[0])));
+        }
+        let command = Command::from_args(words).map_err(line_error)?;
+
+        let task_ids_before = get_task_ids(session)?;
+        outputs.push(command.execute(session).map_err(line_error)?);
+        if let Some(variable) = variable {
+            let created: Vec<TID> = get_task_ids(session)?.difference(&task_ids_before)
+                .copied()
+                .collect();
+            match created[..] {
+                [task_id] => variables.insert(variable, task_id),
+                _ => return Err(line_error(format!(
+                    "${} needs a command which creates exactly one task.",
+                    variable,
+                ))),
+            };
+        }
+    }
+    Ok(outputs)
+}
+
+fn get_task_ids(session: &mut Session) -> Result<HashSet<TID>, String> {
+    match session.load_active_project()? {
+        Some(proj) => Ok(proj.get_tree().get_task_ids().into_iter().collect()),
+        None => Err("No project is currently active.".to_string()),
+    }
+}
+
+/// Parse a line into a statement, without looking at the command itself.
+fn parse_statement(line: &str) -> Result<Statement<'_>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(Statement::Skip);
+    }
+    let assignment = line.strip_prefix('$').and_then(|rest| rest.split_once('='));
+    match assignment {
+        Some((variable, command_line)) => {
+            let variable = variable.trim();
+            if !is_variable_name(variable) {
+                return Err(format!("Invalid variable name ${}.", variable));
+            }
+            if command_line.trim().is_empty() {
+                return Err(format!("Missing the command for ${}.", variable));
+            }
+            Ok(Statement::Run { variable: Some(variable), command_line: command_line.trim() })
+        },
+        None => Ok(Statement::Run { variable: None, command_line: line }),
+    }
+}
+
+/// Replace "$name" with the variable's value. "\$" is left alone, so that `split_words` turns it
+/// into a literal "$".
+fn substitute_variables(line: &str, variables: &HashMap<&str, TID>) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(idx) = rest.find(['$', '\\']) {
+        result.push_str(&rest[..idx]);
+        let (marker, after) = rest[idx..].split_at(1);
+        if marker == "\\" {
+            let escaped_len = after.chars().next().map_or(0, char::len_utf8);
+            result.push_str(&rest[idx..idx + 1 + escaped_len]);
+            rest = &after[escaped_len..];
+            continue;
+        }
+        let name_len = after.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(after.len());
+        let name = &after[..name_len];
+        if !is_variable_name(name) {
+            result.push('$');
+            rest = after;
+            continue;
+        }
+        match variables.get(name) {
+            Some(task_id) => result.push_str(&task_id.to_string()),
+            None => return Err(format!("Undefined variable ${}.", name)),
+        }
+        rest = &after[name_len..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_statement() {
+        assert_eq!(parse_statement("  # comment"), Ok(Statement::Skip));
+        assert_eq!(parse_statement(""), Ok(Statement::Skip));
+        assert_eq!(
+            parse_statement("$api = new \"Build API\""),
+            Ok(Statement::Run { variable: Some("api"), command_line: "new \"Build API\"" }),
+        );
+        assert_eq!(
+            parse_statement("add-dep 1 2"),
+            Ok(Statement::Run { variable: None, command_line: "add-dep 1 2" }),
+        );
+        assert!(parse_statement("$1x = new a").is_err());
+        assert!(parse_statement("$x =").is_err());
+    }
+
+    #[test]
+    fn test_substitute_variables() {
+        let variables = HashMap::from([("ui", 3), ("api", 12)]);
+        assert_eq!(substitute_variables("add-dep $ui $api", &variables).unwrap(), "add-dep 3 12");
+        assert_eq!(
+            substitute_variables("new \"Pay \\$5 for $ui\" $", &variables).unwrap(),
+            "new \"Pay \\$5 for 3\" $",
+        );
+        assert_eq!(
+            substitute_variables("new \"Costs $5\"", &variables).unwrap(),
+            "new \"Costs $5\"",
+        );
+        assert!(substitute_variables("add-dep $ui $db", &variables).is_err());
+    }
+}
@@ -1,25 +1,206 @@
 use crate::project::Project;
+use crate::session::Session;
 use std::io;
-use std::io::Write;
-use crate::tree::TID;
-use ansi_term::Style;
+use std::{ env, fs, process };
+use std::io::{ IsTerminal, Read, Write };
+use crate::tree::{ ExternalRef, ReopenPolicy, Task, TaskStatus, TID };
+use crate::render;
+use crate::stats;
+use crate::timestamp;
+use crate::completion::{ self, Candidate, Shell };
+use crate::shell;
+use crate::batch;
+use crate::config::{ self, ColorMode, Config, ConfirmMode, OutputFormat, Settings, Theme };
+use crate::tui;
+use crate::args::{ self, ArgSpec, Arity, CommandSpec, FlagSpec, ValueKind };
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use ansi_term::{ Colour, Style };
 
 
-pub const GENERAL_USAGE: &str = "usage: tasktree action [args...]";
-const NEW_PROJECT_USAGE: &str = "usage: tasktree new-project project_name project_desc";
-const SWITCH_PROJECT_USAGE: &str = "usage: tasktree switch-project project_name";
-const REMOVE_PROJECT_USAGE: &str = "usage: tasktree rm-project project_name";
+pub const GENERAL_USAGE: &str = "usage: tasktree action [args...]\n\
+                                 Run \"tasktree help\" to list the actions.";
 const NO_ACTIVE_MSG: &str = "No project is currently active. Run \"tasktree switch project_name\" \
                              to switch to a project.";
-const NEW_TASK_USAGE: &str = "usage: tasktree new task_name [task_desc]";
-const REMOVE_TASK_USAGE: &str = "usage: tasktree rm task_id";
-const FIND_TASKS_USAGE: &str = "usage: tasktree find query [status]";
-const VIEW_TASK_USAGE: &str = "usage: tasktree view-task task_id";
-const SET_STATUS_USAGE: &str = "usage: tasktree set task_id new_status";
-const ADD_DEP_USAGE: &str = "usage: tasktree add-dep task_id [dependency_ids...]";
-const ADD_DEP_BTWN_USAGE: &str = "usage: tasktree add-dep-btwn task_id btwn_id dependency_id";
-const REMOVE_DEP_USAGE: &str = "usage: tasktree rm-dep task_id dependency_id";
-const VIEW_DEPS_USAGE: &str = "usage: tasktree view-deps task_id [status]";
+
+/// The command line of each action, in the order they are listed by "tasktree help".
+static COMMANDS: &[CommandSpec] = &[
+    command("new-project", "Create a new project. If the project already exists, asks whether to \
+                            replace it.", &[arg("project_name"), arg("project_desc")],
+        &[YES_FLAG, NO_INPUT_FLAG]),
+    command("rm-project", "Remove a project after asking for confirmation.", &[PROJECT_NAME],
+        &[YES_FLAG, NO_INPUT_FLAG]),
+    command("list-projects", "List all projects.", &[], &[]),
+    command("view-project", "Summarize the active project, including its progress.", &[], &[]),
+    command("switch", "Make the given project the active project.", &[PROJECT_NAME], &[]),
+    command("new", "Create a task in the active project.",
+        &[arg("task_name"), opt_arg("task_desc")], &[]),
+    command("rm", "Remove a task after asking for confirmation.", &[TASK_ID], &[
+        flag("--splice", "Make the task's dependents depend on its dependencies instead."),
+        flag("--recursive", "Also remove the task's dependencies which nothing else needs."),
+        YES_FLAG,
+        NO_INPUT_FLAG,
+    ]),
+    command("view", "List the tasks with the given status (by default, available tasks), or \
+                     \"all\" tasks.", &[opt_arg("status").of_kind(STATUS_OR_ALL)], &[]),
+    command("find", "Find tasks whose name matches the query, optionally with the given status.",
+        &[arg("query"), opt_arg("status").of_kind(STATUS)], &[]),
+    command("view-task", "Show the details of a task.", &[TASK_ID], &[]),
+    command("edit", "Edit a task's description in an editor.", &[TASK_ID], &[]),
+    command("set", "Set a task's status.", &[TASK_ID, arg("new_status").of_kind(STATUS)], &[
+        flag("--cascade", "Also close the task's open dependencies, or reopen the tasks which \
+                           were closed on top of it, after asking for confirmation."),
+        YES_FLAG,
+        NO_INPUT_FLAG,
+    ]),
+    command("add-dep", "Make a task depend on other tasks, which can be in other projects \
+                        (project:task_id).",
+        &[TASK_ID, many_args("dependency_id", 1).of_kind(ValueKind::Dependency)], &[]),
+    command("add-dep-btwn", "Put a task between a task and one of its dependencies.",
+        &[
+            TASK_ID,
+            arg("btwn_id").of_kind(ValueKind::Task),
+            arg("dependency_id").of_kind(ValueKind::Task),
+        ], &[]),
+    command("rm-dep", "Remove a dependency, which can be on a task in another project \
+                       (project:task_id).",
+        &[TASK_ID, arg("dependency_id").of_kind(ValueKind::Dependency)], &[]),
+    command("view-deps", "List a task's dependencies with the given status (by default, \
+                          available ones), or \"all\" of them.",
+        &[TASK_ID, opt_arg("status").of_kind(STATUS_OR_ALL)], &[]),
+    command("tree", "Draw a task (by default, every root task) and its dependencies as a tree.",
+        &[OPT_TASK_ID], &[
+            value_flag("--depth", "N", "Only draw N levels of dependencies."),
+            value_flag("--status", "status", "Only draw tasks with the status (or \"available\"), \
+                                              and the tasks leading to them.")
+                .of_kind(ValueKind::Status(&["all", "available"])),
+        ]),
+    command("export", "Export the dependency graph, or a task's part of it.",
+        &[OPT_TASK_ID], &[
+            value_flag("--format", "dot|mermaid", "The diagram format (by default, dot)."),
+        ]),
+    command("check", "List closed tasks which depend on tasks that aren't closed.", &[], &[]),
+    command("reopen-policy", "View or set what happens when a task which closed tasks depend on \
+                              is reopened.", &[opt_arg("reject|warn|reopen")], &[]),
+    command("move", "Move a task into another project.", &[TASK_ID], &[
+        required_flag("--to", "project_name", "The project to move the task to.")
+            .of_kind(ValueKind::Project),
+        flag("--with-deps", "Also move the task's dependencies."),
+    ]),
+    command("copy", "Copy a task into another project.", &[TASK_ID], &[
+        required_flag("--to", "project_name", "The project to copy the task to.")
+            .of_kind(ValueKind::Project),
+        flag("--with-deps", "Also copy the task's dependencies."),
+    ]),
+    command("merge", "Merge a task into another, after asking for confirmation.",
+        &[arg("keep_id").of_kind(ValueKind::Task), arg("drop_id").of_kind(ValueKind::Task)],
+        &[YES_FLAG, NO_INPUT_FLAG]),
+    command("split", "Replace a task with new tasks with the given names.",
+        &[TASK_ID, many_args("name", 2)], &[]),
+    command("lint-deps", "List dependencies which are implied by other dependencies.", &[], &[
+        flag("--apply", "Remove them."),
+    ]),
+    CommandSpec {
+        name: "workflow",
+        summary: "View or edit the active project's statuses and allowed status changes.",
+        args: &[],
+        flags: &[],
+        subcommands: &[
+            command("add-state", "Add a status.", &[arg("name")], &[
+                flag("--done", "Tasks with the status count as done."),
+                flag("--needs-deps-done", "Tasks can only get the status once their \
+                                           dependencies are done."),
+            ]),
+            command("rm-state", "Remove a status which no task has.",
+                &[arg("name").of_kind(STATUS)], &[]),
+            command("set-transitions", "Set the statuses which tasks with a status can be changed \
+                                        to. Without any, allows every status.",
+                &[arg("from").of_kind(STATUS), many_args("to", 0).of_kind(STATUS)], &[]),
+        ],
+    },
+    command("stats", "Report lead time, cycle time, weekly throughput and aging in-progress \
+                      tasks.", &[], &[
+        value_flag("--weeks", "N", "Report throughput for the last N weeks (by default, 4)."),
+    ]),
+    command("progress", "Show how far along a task (by default, the whole project) is.",
+        &[OPT_TASK_ID], &[]),
+    command("burndown", "Show how many tasks (or a task's dependencies) were open and closed \
+                         over time.", &[OPT_TASK_ID], &[
+        value_flag("--since", "YYYY-MM-DD", "Start at this date (by default, when the oldest task \
+                                             was created)."),
+        value_flag("--bucket", "day|week", "Count per day (the default) or per week."),
+        value_flag("--format", "chart|csv|json", "The output format (by default, chart)."),
+    ]),
+    command("shell", "Start an interactive shell which runs actions without reloading the active \
+                      project each time.", &[], &[]),
+    command("batch", "Run the commands in a file (by default, stdin), one per line, and save the \
+                      active project once if they all succeed.", &[opt_arg("file")], &[]),
+    command("tui", "Browse and edit the active project's tasks in a full-screen terminal UI.",
+        &[], &[]),
+    command("completions", "Print a completion script for the given shell.",
+        &[arg("bash|zsh|fish")], &[]),
+    CommandSpec {
+        name: "config",
+        summary: "List the settings, or get, set or unset one. Settings are set for every project, \
+                  or with \"--project\", only for one project.",
+        args: &[],
+        flags: &[CONFIG_PROJECT_FLAG],
+        subcommands: &[
+            command("get", "Show a setting's value.", &[arg("key")], &[CONFIG_PROJECT_FLAG]),
+            command("set", "Change a setting.", &[arg("key"), arg("value")],
+                &[CONFIG_PROJECT_FLAG]),
+            command("unset", "Go back to a setting's default.", &[arg("key")],
+                &[CONFIG_PROJECT_FLAG]),
+        ],
+    },
+    command("help", "Show the help for an action, or list the actions.",
+        &[opt_arg("action").of_kind(ValueKind::Action), opt_arg("subcommand")], &[]),
+];
+
+const YES_FLAG: FlagSpec = flag("--yes", "Confirm without asking.");
+const NO_INPUT_FLAG: FlagSpec = flag("--no-input", "Fail instead of asking for confirmation. This \
+                                                    is the default when stdin isn't a terminal.");
+const CONFIG_PROJECT_FLAG: FlagSpec = value_flag("--project", "project_name", "The project to \
+                                                  use instead of the active project.")
+    .of_kind(ValueKind::Project);
+const PROJECT_NAME: ArgSpec = arg("project_name").of_kind(ValueKind::Project);
+const TASK_ID: ArgSpec = arg("task_id").of_kind(ValueKind::Task);
+const OPT_TASK_ID: ArgSpec = opt_arg("task_id").of_kind(ValueKind::Task);
+const STATUS: ValueKind = ValueKind::Status(&[]);
+const STATUS_OR_ALL: ValueKind = ValueKind::Status(&["all"]);
+
+const fn command(
+    name: &'static str,
+    summary: &'static str,
+    args: &'static [ArgSpec],
+    flags: &'static [FlagSpec],
+) -> CommandSpec {
+    CommandSpec { name, summary, args, flags, subcommands: &[] }
+}
+
+const fn arg(name: &'static str) -> ArgSpec {
+    ArgSpec { name, arity: Arity::Required, kind: ValueKind::Text }
+}
+
+const fn opt_arg(name: &'static str) -> ArgSpec {
+    ArgSpec { name, arity: Arity::Optional, kind: ValueKind::Text }
+}
+
+const fn many_args(name: &'static str, min: usize) -> ArgSpec {
+    ArgSpec { name, arity: Arity::Repeated { min }, kind: ValueKind::Text }
+}
+
+const fn flag(name: &'static str, help: &'static str) -> FlagSpec {
+    FlagSpec { name, value: None, required: false, help, kind: ValueKind::Text }
+}
+
+const fn value_flag(name: &'static str, value: &'static str, help: &'static str) -> FlagSpec {
+    FlagSpec { name, value: Some(value), required: false, help, kind: ValueKind::Text }
+}
+
+const fn required_flag(name: &'static str, value: &'static str, help: &'static str) -> FlagSpec {
+    FlagSpec { name, value: Some(value), required: true, help, kind: ValueKind::Text }
+}
 
 
 /// Enum representing an action the user would like to execute.
@@ -35,11 +216,34 @@ enum Action {
     ViewTasks,
     FindTasks,
     ViewTask,
+    EditTask,
     SetStatus,
     AddDep,
     AddDepBtwn,
     RemoveDep,
     ViewDeps,
+    Tree,
+    Export,
+    Check,
+    ReopenPolicy,
+    MoveTask,
+    CopyTask,
+    MergeTasks,
+    SplitTask,
+    LintDeps,
+    Workflow,
+    Stats,
+    Progress,
+    Burndown,
+    Shell,
+    Batch,
+    Tui,
+    Completions,
+    Config,
+    /// Print the completions for a partial command line. Called by the completion scripts, and
+    /// not listed by "tasktree help".
+    Complete,
+    Help,
 }
 
 impl Action {
@@ -56,66 +260,189 @@ impl Action {
             "view" => Ok(Self::ViewTasks),
             "find" => Ok(Self::FindTasks),
             "view-task" => Ok(Self::ViewTask),
+            "edit" => Ok(Self::EditTask),
             "set" => Ok(Self::SetStatus),
             "add-dep" => Ok(Self::AddDep),
             "add-dep-btwn" => Ok(Self::AddDepBtwn),
             "rm-dep" => Ok(Self::RemoveDep),
             "view-deps" => Ok(Self::ViewDeps),
-            _ => Err(format!("no action \"{}\"", arg)),
+            "tree" => Ok(Self::Tree),
+            "export" => Ok(Self::Export),
+            "check" => Ok(Self::Check),
+            "reopen-policy" => Ok(Self::ReopenPolicy),
+            "move" => Ok(Self::MoveTask),
+            "copy" => Ok(Self::CopyTask),
+            "merge" => Ok(Self::MergeTasks),
+            "split" => Ok(Self::SplitTask),
+            "lint-deps" => Ok(Self::LintDeps),
+            "workflow" => Ok(Self::Workflow),
+            "stats" => Ok(Self::Stats),
+            "progress" => Ok(Self::Progress),
+            "burndown" => Ok(Self::Burndown),
+            "shell" => Ok(Self::Shell),
+            "batch" => Ok(Self::Batch),
+            "tui" => Ok(Self::Tui),
+            "completions" => Ok(Self::Completions),
+            "config" => Ok(Self::Config),
+            "__complete" => Ok(Self::Complete),
+            "help" => Ok(Self::Help),
+            _ => {
+                let names: Vec<&str> = COMMANDS.iter().map(|spec| spec.name).collect();
+                Err(format!("no action \"{}\"{}", arg, args::did_you_mean(arg, &names)))
+            },
         }
     }
 
 }
 
 
+/// A dependency given on the command line: either a task in the active project, or a task in
+/// another project given as "project:task_id".
+enum DependencyArg {
+    Local(TID),
+    External(ExternalRef),
+}
+
+
 /// Enum representing a user's command.
 pub struct Command {
     action: Action,
+    subcommand: Option<&'static str>,
+    /// Positional arguments.
     args: Vec<String>,
+    /// Flags, mapped to their values ("" for flags without a value).
+    flags: HashMap<String, String>,
 }
 
 impl Command {
 
+    /// Parse a command line (without the program name) according to the action's `CommandSpec`.
+    /// "--help" (or "-h") anywhere in the command line asks for the action's help instead.
     pub fn from_args(args: Vec<String>) -> Result<Self, String> {
-        if args.len() < 1 {
+        if args.is_empty() {
             return Err(GENERAL_USAGE.to_string());
         }
+        if args[0] == "--help" || args[0] == "-h" {
+            return Ok(Self::help_command(Vec::new()));
+        }
+        if args[0] == "__complete" {
+            return Ok(Command {
+                action: Action::Complete,
+                subcommand: None,
+                args: args[1..].to_vec(),
+                flags: HashMap::new(),
+            });
+        }
 
+        let args = Self::expand_alias(args)?;
         let action = Action::from_cmdline_arg(&args[0])?;
-        let command_args = match args.len() {
-            1 => Vec::new(),
-            _ => args[1..].to_vec(),
-        };
+        let spec = Self::get_spec(&args[0]).unwrap();
+        let command_args = &args[1..];
+        let wants_help = command_args.iter()
+            .take_while(|arg| *arg != "--")
+            .any(|arg| arg == "--help" || arg == "-h");
+        if wants_help {
+            let mut help_args = vec![args[0].clone()];
+            if let Some(first) = command_args.first() {
+                if spec.subcommands.iter().any(|subcommand| subcommand.name == first) {
+                    help_args.push(first.clone());
+                }
+            }
+            return Ok(Self::help_command(help_args));
+        }
 
-        Ok(Command{ action, args: command_args })
+        let parsed = args::parse(spec, command_args)?;
+        Ok(Command {
+            action,
+            subcommand: parsed.subcommand,
+            args: parsed.positional,
+            flags: parsed.flags,
+        })
     }
 
-    /// Run this command.
-    pub fn execute(&self) -> Result<String, String> {
+    /// Expand the alias which a command line (without the program name) starts with, if any.
+    /// Actions can't be replaced by aliases.
+    pub fn expand_alias(args: Vec<String>) -> Result<Vec<String>, String> {
+        if args.is_empty() || Self::get_spec(&args[0]).is_some() {
+            return Ok(args);
+        }
+        Self::expand_alias_with(args, &config::load_settings(Project::get_active().as_deref()))
+    }
+
+    /// Expand the alias which a command line starts with, looking it up in the given settings.
+    fn expand_alias_with(args: Vec<String>, settings: &Settings) -> Result<Vec<String>, String> {
+        if args.is_empty() || Self::get_spec(&args[0]).is_some() {
+            return Ok(args);
+        }
+        match settings.get_alias(&args[0]) {
+            Some(command_line) => {
+                args::expand_alias(&args[0], command_line, &args[1..], COMMANDS)
+            },
+            None => Ok(args),
+        }
+    }
+
+    /// Complete the last of the given words, which are a command line (without the program name)
+    /// as far as it has been typed.
+    pub fn complete(words: &[String]) -> Vec<Candidate> {
+        completion::complete(COMMANDS, words)
+    }
+
+    fn help_command(args: Vec<String>) -> Self {
+        Command { action: Action::Help, subcommand: None, args, flags: HashMap::new() }
+    }
+
+    fn get_spec(name: &str) -> Option<&'static CommandSpec> {
+        COMMANDS.iter().find(|spec| spec.name == name)
+    }
+
+    /// Run this command with the settings for the active project, loading and saving projects
+    /// through the session.
+    pub fn execute(&self, session: &mut Session) -> Result<String, String> {
+        config::use_settings(config::load_settings(Project::get_active().as_deref()));
         match self.action {
-            Action::NewProject => self.new_project_action(),
-            Action::RemoveProject => self.remove_project_action(),
+            Action::NewProject => self.new_project_action(session),
+            Action::RemoveProject => self.remove_project_action(session),
             Action::ListProjects => self.list_projects_action(),
-            Action::ViewProject => self.view_project_action(),
+            Action::ViewProject => self.view_project_action(session),
             Action::SwitchProject => self.switch_project_action(),
-            Action::NewTask => self.new_task_action(),
-            Action::RemoveTask => self.remove_task_action(),
-            Action::ViewTasks => self.view_tasks_action(),
-            Action::FindTasks => self.find_tasks_action(),
-            Action::ViewTask => self.view_task_action(),
-            Action::SetStatus => self.set_status_action(),
-            Action::AddDep => self.add_dep_action(),
-            Action::AddDepBtwn => self.add_dep_btwn_action(),
-            Action::RemoveDep => self.remove_dep_action(),
-            Action::ViewDeps => self.view_deps_action(),
+            Action::NewTask => self.new_task_action(session),
+            Action::RemoveTask => self.remove_task_action(session),
+            Action::ViewTasks => self.view_tasks_action(session),
+            Action::FindTasks => self.find_tasks_action(session),
+            Action::ViewTask => self.view_task_action(session),
+            Action::EditTask => self.edit_task_action(session),
+            Action::SetStatus => self.set_status_action(session),
+            Action::AddDep => self.add_dep_action(session),
+            Action::AddDepBtwn => self.add_dep_btwn_action(session),
+            Action::RemoveDep => self.remove_dep_action(session),
+            Action::ViewDeps => self.view_deps_action(session),
+            Action::Tree => self.tree_action(session),
+            Action::Export => self.export_action(session),
+            Action::Check => self.check_action(session),
+            Action::ReopenPolicy => self.reopen_policy_action(session),
+            Action::MoveTask => self.transfer_task_action(session, true),
+            Action::CopyTask => self.transfer_task_action(session, false),
+            Action::MergeTasks => self.merge_tasks_action(session),
+            Action::SplitTask => self.split_task_action(session),
+            Action::LintDeps => self.lint_deps_action(session),
+            Action::Workflow => self.workflow_action(session),
+            Action::Stats => self.stats_action(session),
+            Action::Progress => self.progress_action(session),
+            Action::Burndown => self.burndown_action(session),
+            Action::Shell => shell::run(session),
+            Action::Batch => self.batch_action(session),
+            Action::Tui => tui::run(Self::load_active_project_resolved(session)?, session),
+            Action::Completions => self.completions_action(),
+            Action::Config => self.config_action(),
+            Action::Complete => self.complete_action(),
+            Action::Help => self.help_action(),
         }
     }
 
     /// Create a new project with the given project name and description. If the project already
     /// exists, prompt the user ("y"/"n") to confirm replacement.
-    fn new_project_action(&self) -> Result<String, String> {
-        self.check_args_len(2, NEW_PROJECT_USAGE)?;
-
+    fn new_project_action(&self, session: &mut Session) -> Result<String, String> {
         let project_name = &self.args[0];
         let project_desc = &self.args[1];
         let already_exists_msg = &format!("The project {} already exists. Are you sure you would \
@@ -124,16 +451,12 @@ impl Command {
 
         let mut replace_project = true;
         if Project::exists(project_name)? {
-            let user_input = Self::get_user_input(already_exists_msg, vec!["y", "n"]);
-            replace_project = match &user_input[..] {
-                "y" => true,
-                "n" => false,
-                _ => panic!("Invalid user input allowed."),
-            };
+            replace_project = self.confirm(already_exists_msg)?;
         }
 
         if replace_project {
             Project::new(project_name.to_string(), project_desc.to_string());
+            session.forget();
             return Ok(format!("Successfully created project {}.", project_name));
         }
 
@@ -143,9 +466,7 @@ impl Command {
     /// Remove the project with the given name. If the project does not exist, return an error
     /// informing the user of this. Otherwise, prompt the user to confirm ("y"/"n") to confirm the
     /// removal, and remove it if "y".
-    fn remove_project_action(&self) -> Result<String, String> {
-        self.check_args_len(1, REMOVE_PROJECT_USAGE)?;
-
+    fn remove_project_action(&self, session: &mut Session) -> Result<String, String> {
         let project_name = &self.args[0];
         if !Project::exists(project_name)? {
             return Err(format!("There is no project named {}.", project_name))
@@ -153,26 +474,21 @@ impl Command {
 
         let prompt_msg = &format!("Are you sure you want to delete the project {}? This operation \
                                    cannot be undone (y/n) ", project_name);
-        match &Self::get_user_input(prompt_msg, vec!["y", "n"])[..] {
-            "y" =>  {
-                Project::remove(project_name)?;
-                return Ok(format!("Successfully removed project {}.", project_name));
-            },
-            "n" => Ok(format!("Did not remove project {}.", project_name)),
-            _ => panic!("Disallowed input provided"),
+        if !self.confirm(prompt_msg)? {
+            return Ok(format!("Did not remove project {}.", project_name));
         }
+        Project::remove(project_name)?;
+        session.forget();
+        Ok(format!("Successfully removed project {}.", project_name))
     }
 
     /// List existing tasktree project names.
     fn list_projects_action(&self) -> Result<String, String> {
         let proj_list = Project::get_project_names()?;
-        if proj_list.len() == 0 {
+        if proj_list.is_empty() {
             return Err("no tasktree projects. create one: \"tasktree new-project\"".to_string());
         }
-        let mut result = String::from(format!(
-            "{}",
-            bold_text(&underline_text("tasktree projects:"))
-        ));
+        let mut result = bold_text(&underline_text("tasktree projects:"));
 
         for proj_name in proj_list {
             result.push_str(&format!("\n{}", &proj_name));
@@ -182,43 +498,43 @@ impl Command {
 
     // Provider a summary of the active project. If there is no active project, return an error
     // message informing the user of this. Otherwise, return a summary of the active project.
-    fn view_project_action(&self) -> Result<String, String> {
-        match Project::get_active() {
-            None => Err(NO_ACTIVE_MSG.to_string()),
-            Some(proj_name) => {
-                let proj = Project::load(&proj_name)?;
-                let mut info = format!("{}\n", underline_text("Project Info"));
-
-                info.push_str(&format!(
-                    "{}: {}\n",
-                    bold_text("name"),
-                    proj_name,
-                ));
-                info.push_str(&format!(
-                    "{}: {}\n",
-                    bold_text("created"),
-                    proj.get_created_timestamp(),
-                ));
-                info.push_str(&format!(
-                    "{}: {}\n",
-                    bold_text("modified"),
-                    proj.get_modified_timestamp(),
-                ));
-                info.push_str(&format!(
-                    "{}: {}",
-                    bold_text("description"),
-                    proj.get_desc(),
-                ));
-                Ok(info)
-            }
-        }
+    fn view_project_action(&self, session: &mut Session) -> Result<String, String> {
+        let proj = Self::load_active_project(session)?;
+        let mut info = format!("{}\n", underline_text("Project Info"));
+
+        info.push_str(&format!(
+            "{}: {}\n",
+            bold_text("name"),
+            proj.get_name(),
+        ));
+        info.push_str(&format!(
+            "{}: {}\n",
+            bold_text("created"),
+            timestamp::format_local(proj.get_created_timestamp()),
+        ));
+        info.push_str(&format!(
+            "{}: {}\n",
+            bold_text("modified"),
+            timestamp::format_local(proj.get_modified_timestamp()),
+        ));
+        info.push_str(&format!(
+            "{}: {}\n",
+            bold_text("description"),
+            proj.get_desc(),
+        ));
+        let tree = proj.get_tree();
+        info.push_str(&format!(
+            "{}:\n{}",
+            bold_text("progress"),
+            stats::format_progress(tree, &tree.get_task_ids()),
+        ));
+        Ok(info)
     }
 
     /// Switch the active project to the project with the given name. If no such project exists,
     /// return an error messaging informing the user. If successful, return a message confirming
     /// that the active project has been switched.
     fn switch_project_action(&self) -> Result<String, String> {
-        self.check_args_len(1, SWITCH_PROJECT_USAGE)?;
         let project_name = &self.args[0];
         Project::set_active(project_name)?;
 
@@ -228,15 +544,14 @@ impl Command {
     /// Create a task in the active project with the given name and optional description. If
     /// anything fails, returns appropriate error message. Otherwise, create the task, save the
     /// project, and return a message confirming that the new task was created.
-    fn new_task_action(&self) -> Result<String, String> {
-        self.check_args_len(1, NEW_TASK_USAGE)?;
+    fn new_task_action(&self, session: &mut Session) -> Result<String, String> {
         let task_name = &self.args[0];
         let task_desc = self.parse_optional_argument(1);
         
-        let mut proj = Self::load_active_project()?;
+        let mut proj = Self::load_active_project(session)?;
         let tasks = proj.get_tree_mut();
         let task_id = tasks.add_task(task_name.to_string(), task_desc);
-        proj.save()?;
+        session.save(&mut proj)?;
 
         Ok(format!("Created task {} with id {}.", task_name, task_id))
     }
@@ -244,10 +559,18 @@ impl Command {
     /// Remove the task with the given id from the active project. If such a task does not exist,
     /// return an error message indicating this to the user. Otherwise, require the user to confirm
     /// ("y"/"n") to remove the task. If "y", deletes the task and informs the user.
-    fn remove_task_action(&self) -> Result<String, String> {
-        self.check_args_len(1, REMOVE_TASK_USAGE)?;
-        let task_id = Self::parse_as_task_id(&self.args[0])?;
-        let mut proj = Self::load_active_project()?;
+    ///
+    /// With "--splice", the task's dependents are made to depend on its dependencies instead. With
+    /// "--recursive", the task's dependencies which nothing else needs are removed as well. Either
+    /// way, the resulting changes are listed before asking for confirmation.
+    fn remove_task_action(&self, session: &mut Session) -> Result<String, String> {
+        let splice = self.flags.contains_key("--splice");
+        let recursive = self.flags.contains_key("--recursive");
+        if splice && recursive {
+            return Err("--splice and --recursive cannot be used together.".to_string());
+        }
+        let mut proj = Self::load_active_project(session)?;
+        let task_id = Self::parse_as_task_id(&proj, &self.args[0])?;
         let tasks = proj.get_tree_mut();
         let task_repr = match tasks.get_task_repr(&task_id) {
             Some(task_repr) => task_repr,
@@ -257,50 +580,82 @@ impl Command {
             )),
         };
 
-        let prompt = format!(
+        let mut prompt = String::new();
+        if splice {
+            let new_edges = tasks.preview_splice(&task_id)?;
+            if !new_edges.is_empty() {
+                prompt.push_str("The following dependencies will be added:\n");
+            }
+            for (parent_id, child_id) in new_edges {
+                prompt.push_str(&format!(
+                    "task {} on task {}\n",
+                    bold_tid(parent_id),
+                    bold_tid(child_id),
+                ));
+            }
+        } else if recursive {
+            prompt.push_str("The following tasks will be removed:\n");
+            for removed_id in tasks.preview_remove_recursive(&task_id)? {
+                prompt.push_str(&format!("{}\n", tasks.get_task_repr(&removed_id).unwrap()));
+            }
+        }
+        prompt.push_str(&format!(
             "Are you sure you want to remove the task '{}' from the active project (y/n)? ",
             task_repr
-        );
-        let user_input = Self::get_user_input(&prompt, vec!["y", "n"]);
-        match &user_input[..] {
-            "y" => {
-                tasks.remove_task(&task_id)?;
-                proj.save()?;
-                Ok(format!("Successfully removed task {}.", bold_tid(task_id)))
-            },
-            "n" => Ok(format!("Did not remove task {}.", bold_tid(task_id))),
-            _ => panic!("Invalid user input"),
+        ));
+        if !self.confirm(&prompt)? {
+            return Ok(format!("Did not remove task {}.", bold_tid(task_id)));
         }
+        let mut result = format!("Successfully removed task {}.", bold_tid(task_id));
+        if splice {
+            let new_edges = tasks.remove_task_splice(&task_id)?;
+            result.push_str(&format!(" Added {} dependencies.", new_edges.len()));
+        } else if recursive {
+            let removed = tasks.remove_task_recursive(&task_id)?;
+            result.push_str(&format!(
+                " Removed {} unneeded dependencies.",
+                removed.len() - 1,
+            ));
+        } else {
+            tasks.remove_task(&task_id)?;
+        }
+        session.save(&mut proj)?;
+        Ok(result)
     }
 
     /// View the tasks in the active project which match the given status flag. By default, the
     /// status flag is "available". If there are no matching tasks, inform the user.
-    fn view_tasks_action(&self) -> Result<String, String> {
-        let proj = Self::load_active_project()?;
+    fn view_tasks_action(&self, session: &mut Session) -> Result<String, String> {
+        let proj = Self::load_active_project_resolved(session)?;
         let tasks = proj.get_tree();
         let mut result = String::new();
-        let status_flag = self.parse_optional_argument(0);
+        let status_flag = self.parse_optional_argument(0)
+            .or_else(|| config::with_active(|settings| settings.default_status.clone()))
+            .filter(|status_flag| status_flag != "available");
         let status_flag_name = match &status_flag {
             None => "available",
-            Some(x) => &x,
+            Some(x) => x,
         };
 
         let matches = tasks.view_tasks(status_flag.clone())?;
-        if matches.len() == 0 {
+        if Self::is_json_output() {
+            return Ok(serde_json::to_string_pretty(&matches).unwrap());
+        }
+        if matches.is_empty() {
             return Err(format!(
                 "no {} tasks in project {}",
-                bold_text(&status_flag_name),
+                bold_text(status_flag_name),
                 bold_text(proj.get_name()),
             ));
         }
         result.push_str(&format!(
             "{} tasks in project {}:",
-            bold_text(&status_flag_name),
+            bold_text(status_flag_name),
             bold_text(proj.get_name()),
         ));
         for _match in matches {
-            result.push_str("\n");
-            result.push_str(&_match.get_repr());
+            result.push('\n');
+            result.push_str(&styled_repr(_match));
         }
 
         Ok(result)
@@ -308,22 +663,24 @@ impl Command {
 
     /// Find tasks in the active project which match the provided query and the optionally provided
     /// status. If no tasks match the query, inform the user.
-    fn find_tasks_action(&self) -> Result<String, String> {
-        self.check_args_len(1, FIND_TASKS_USAGE)?;
-        let proj = Self::load_active_project()?;
+    fn find_tasks_action(&self, session: &mut Session) -> Result<String, String> {
+        let proj = Self::load_active_project(session)?;
         let tasks = proj.get_tree();
         let query = self.args[0].to_string();
         let status_flag = self.parse_optional_argument(1);
         let status_flag_name = match &status_flag {
             None => "all",
-            Some(x) => &x,
+            Some(x) => x,
         };
         let mut result = String::new();
         let matches = tasks.search_tasks(&query, status_flag.clone())?;
-        if matches.len() == 0 {
+        if Self::is_json_output() {
+            return Ok(serde_json::to_string_pretty(&matches).unwrap());
+        }
+        if matches.is_empty() {
             result.push_str(&format!(
                 "no {} tasks for query '{}' in project {}",
-                bold_text(&status_flag_name),
+                bold_text(status_flag_name),
                 bold_text(&query),
                 bold_text(proj.get_name()),
             ));
@@ -332,57 +689,119 @@ impl Command {
 
         result.push_str(&format!(
             "{} tasks for query '{}' in project {}:\n",
-            bold_text(&status_flag_name),
+            bold_text(status_flag_name),
             bold_text(&query),
             bold_text(proj.get_name()),
         ));
         for _match in matches {
-            result.push_str(&_match);
-            result.push_str("\n");
+            result.push_str(&styled_repr(_match));
+            result.push('\n');
         }
         Ok(result.trim().to_string())
     }
 
     /// View a detailed summary of the task with the given ID. If no such task exists, inform the
     /// user with an error message.
-    fn view_task_action(&self) -> Result<String, String> {
-        self.check_args_len(1, VIEW_TASK_USAGE)?;
-        let task_id = Self::parse_as_task_id(&self.args[0])?;
-        let proj = Self::load_active_project()?;
-        let tasks = proj.get_tree();
-        tasks.view_task(&task_id)
+    fn view_task_action(&self, session: &mut Session) -> Result<String, String> {
+        let proj = Self::load_active_project(session)?;
+        let task_id = Self::parse_as_task_id(&proj, &self.args[0])?;
+        proj.get_tree().view_task(&task_id)
     }
 
-    /// Set the task with the given id's status to the given status.
-    fn set_status_action(&self) -> Result<String, String> {
-        self.check_args_len(2, SET_STATUS_USAGE)?;
-        let task_id = Self::parse_as_task_id(&self.args[0])?;
-        let status = &self.args[1];
+    /// Edit the description of the task with the given ID in the editor given by the "editor"
+    /// setting (by default, $VISUAL or $EDITOR). An empty description removes it.
+    fn edit_task_action(&self, session: &mut Session) -> Result<String, String> {
+        let mut proj = Self::load_active_project(session)?;
+        let task_id = Self::parse_as_task_id(&proj, &self.args[0])?;
+        let desc = match proj.get_tree().get_task(&task_id) {
+            Some(task) => task.get_desc().map(str::to_string),
+            None => return Err(format!(
+                "There is no task for the active project with id {}.",
+                task_id
+            )),
+        };
+
+        let edited = Self::edit_text(desc.as_deref().unwrap_or(""))?;
+        let new_desc = Some(edited.trim().to_string()).filter(|new_desc| !new_desc.is_empty());
+        if new_desc == desc {
+            return Ok(format!("Task {}'s description didn't change.", bold_tid(task_id)));
+        }
+        proj.get_tree_mut().set_desc(&task_id, new_desc)?;
+        session.save(&mut proj)?;
+        Ok(format!("Updated task {}'s description.", bold_tid(task_id)))
+    }
 
-        let mut proj = Self::load_active_project()?;
+    /// Set the task with the given id's status to the given status. With "--cascade", closing the
+    /// task also closes its open dependencies, and reopening it also reopens the dependents which
+    /// were closed on top of it. The user is asked to confirm ("y"/"n") any cascaded changes.
+    fn set_status_action(&self, session: &mut Session) -> Result<String, String> {
+        let status = &self.args[1];
+        let mut proj = Self::load_active_project_resolved(session)?;
+        let task_id = Self::parse_as_task_id(&proj, &self.args[0])?;
         let tasks = proj.get_tree_mut();
-        tasks.set_status(&task_id, status.to_string())?;
-        proj.save()?;
-        Ok(format!("Set task {}'s status to {}.", 
+        if !self.flags.contains_key("--cascade") {
+            let closed_dependents = tasks.set_status(&task_id, status.to_string())?;
+            let reopen_policy = tasks.get_reopen_policy();
+            session.save(&mut proj)?;
+            let mut result = format!("Set task {}'s status to {}.",
+                bold_tid(task_id),
+                bold_text(status)
+            );
+            if !closed_dependents.is_empty() {
+                let dependent_ids: Vec<String> = closed_dependents.iter()
+                    .map(|id| bold_tid(*id))
+                    .collect();
+                match reopen_policy {
+                    ReopenPolicy::Reopen => result.push_str(&format!(
+                        "\nAlso reopened tasks which depend on it: {}.",
+                        dependent_ids.join(", "),
+                    )),
+                    _ => result.push_str(&format!(
+                        "\nWarning: closed tasks depend on it: {}. Run \"tasktree check\" to \
+                         list inconsistencies.",
+                        dependent_ids.join(", "),
+                    )),
+                }
+            }
+            return Ok(result);
+        }
+
+        let affected = tasks.preview_status_cascade(&task_id, status)?;
+        let others: Vec<&TID> = affected.iter().filter(|id| **id != task_id).collect();
+        if !others.is_empty() {
+            let mut prompt = format!(
+                "Setting task {}'s status to {} will also change the following tasks:",
+                bold_tid(task_id),
+                bold_text(status),
+            );
+            for other_id in &others {
+                prompt.push_str(&format!("\n{}", tasks.get_task_repr(other_id).unwrap()));
+            }
+            prompt.push_str("\nContinue (y/n)? ");
+            if !self.confirm(&prompt)? {
+                return Ok(format!("Did not set task {}'s status.", bold_tid(task_id)));
+            }
+        }
+        tasks.set_status_cascade(&task_id, status.to_string())?;
+        session.save(&mut proj)?;
+        Ok(format!("Set task {}'s status to {} ({} other tasks changed).",
             bold_tid(task_id),
-            bold_text(status)
+            bold_text(status),
+            others.len(),
         ))
     }
 
     /// Add a dependency of the task with the first provided task id (task_id) on the tasks with 
     /// the provided other task ids (depends_on_id). Requires that this does not create a cycle.
-    fn add_dep_action(&self) -> Result<String, String> {
-        self.check_args_len(2, ADD_DEP_USAGE)?;
-        let task_id = Self::parse_as_task_id(&self.args[0])?;
-
+    /// Tasks in other projects can be given as "project:task_id".
+    fn add_dep_action(&self, session: &mut Session) -> Result<String, String> {
+        let mut proj = Self::load_active_project(session)?;
+        let task_id = Self::parse_as_task_id(&proj, &self.args[0])?;
         let mut dep_ids = Vec::new();
-        for dep_id_str in self.args[1..].into_iter() {
-            dep_ids.push(Self::parse_as_task_id(dep_id_str)?);
+        for dep_id_str in self.args[1..].iter() {
+            dep_ids.push(Self::parse_as_dependency(&proj, dep_id_str)?);
         }
 
-        let mut proj = Self::load_active_project()?;
-        let tasks = proj.get_tree_mut();
-
         let mut result = if dep_ids.len() == 1 {
             String::from("Added task ")
         } else {
@@ -390,8 +809,16 @@ impl Command {
         };
 
         for dep_id in &dep_ids {
-            tasks.add_dependency(&task_id, &dep_id)?;
-            result.push_str(&format!("{} ", bold_tid(*dep_id)));
+            match dep_id {
+                DependencyArg::Local(dep_id) => {
+                    proj.add_dependency(&task_id, dep_id)?;
+                    result.push_str(&format!("{} ", bold_tid(*dep_id)));
+                },
+                DependencyArg::External(external_ref) => {
+                    proj.add_external_dependency(&task_id, external_ref.clone())?;
+                    result.push_str(&format!("{} ", bold_text(&external_ref.to_string())));
+                },
+            }
         }
 
         if dep_ids.len() == 1 {
@@ -400,59 +827,65 @@ impl Command {
             result.push_str(&format!("as dependencies for task {}.", bold_tid(task_id)));
         };
 
-        proj.save()?;
+        session.save(&mut proj)?;
         Ok(result)
     }
 
     /// Takes three task ids: task_id, new_id, and depends_on_id. Requires that task_id depends on
     /// depends_on_id. Then removes this dependency, and add a dependencies for task_id on new_id 
     /// and for new_id on depends_on_id.
-    fn add_dep_btwn_action(&self) -> Result<String, String> {
-        self.check_args_len(3, ADD_DEP_BTWN_USAGE)?;
-        let task_id = Self::parse_as_task_id(&self.args[0])?;
-        let new_id = Self::parse_as_task_id(&self.args[1])?;
-        let depends_on_id = Self::parse_as_task_id(&self.args[2])?;
-        let mut proj = Self::load_active_project()?;
+    fn add_dep_btwn_action(&self, session: &mut Session) -> Result<String, String> {
+        let mut proj = Self::load_active_project(session)?;
+        let task_id = Self::parse_as_task_id(&proj, &self.args[0])?;
+        let new_id = Self::parse_as_task_id(&proj, &self.args[1])?;
+        let depends_on_id = Self::parse_as_task_id(&proj, &self.args[2])?;
         let tasks = proj.get_tree_mut();
         tasks.add_dependency_btwn(&task_id, &new_id, &depends_on_id)?;
 
-        proj.save()?;
+        session.save(&mut proj)?;
         Ok(format!("Added task {} between {} and {}.", new_id, task_id, depends_on_id))
     }
 
     /// Removes a of task_id on dependency_id if the dependency and both tasks exist.
-    fn remove_dep_action(&self) -> Result<String, String> {
-        self.check_args_len(2, REMOVE_DEP_USAGE)?;
-        let task_id = Self::parse_as_task_id(&self.args[0])?;
-        let dependency_id = Self::parse_as_task_id(&self.args[1])?;
-
-        let mut proj = Self::load_active_project()?;
+    fn remove_dep_action(&self, session: &mut Session) -> Result<String, String> {
+        let mut proj = Self::load_active_project(session)?;
+        let task_id = Self::parse_as_task_id(&proj, &self.args[0])?;
+        let dependency_id = Self::parse_as_dependency(&proj, &self.args[1])?;
         let tasks = proj.get_tree_mut();
-        tasks.remove_dependency(&task_id, &dependency_id)?;
+        let dependency_name = match dependency_id {
+            DependencyArg::Local(dependency_id) => {
+                tasks.remove_dependency(&task_id, &dependency_id)?;
+                dependency_id.to_string()
+            },
+            DependencyArg::External(external_ref) => {
+                tasks.remove_external_dependency(&task_id, &external_ref)?;
+                external_ref.to_string()
+            },
+        };
+        session.save(&mut proj)?;
 
-        Ok(format!("Removed dependency of task {} on task {}.", task_id, dependency_id))
+        Ok(format!("Removed dependency of task {} on task {}.", task_id, dependency_name))
     }
 
     /// View the dependencies for the provided task id which match the given status flag. By 
     /// default, the status flag is "available". If there are no matching tasks, informs the user.
-    fn view_deps_action(&self) -> Result<String, String> {
-        self.check_args_len(1, VIEW_DEPS_USAGE)?;
-        let task_id = Self::parse_as_task_id(&self.args[0])?;
-        let opt_status_flag = if self.args.len() > 1 {
-            Some(self.args[1].to_string())
-        } else {
-            None
-        };
+    fn view_deps_action(&self, session: &mut Session) -> Result<String, String> {
+        let opt_status_flag = self.parse_optional_argument(1);
         let status_flag_name = match opt_status_flag {
             None => "available".to_string(),
             Some(ref x) => x.clone(),
         };
 
-        let proj = Self::load_active_project()?;
+        let proj = Self::load_active_project_resolved(session)?;
+        let task_id = Self::parse_as_task_id(&proj, &self.args[0])?;
         let tree = proj.get_tree();
-        let dep_ids = tree.get_dependencies(&task_id, opt_status_flag)?;
+        let dep_ids = tree.get_dependencies(&task_id, opt_status_flag.clone())?;
+        let external_reprs = Self::get_external_dep_reprs(
+            tree.get_external_dependencies(&task_id)?,
+            opt_status_flag,
+        )?;
         let mut result = String::new();
-        if dep_ids.len() == 0 {
+        if dep_ids.is_empty() && external_reprs.is_empty() {
             return Ok(format!(
                 "no {} dependencies task {}",
                 bold_text(&status_flag_name),
@@ -465,60 +898,725 @@ impl Command {
             bold_tid(task_id),
         ));
         for dep_id in dep_ids {
-            result.push_str("\n");
+            result.push('\n');
             result.push_str(&tree.get_task_repr(dep_id).unwrap());
         }
+        for external_repr in external_reprs {
+            result.push('\n');
+            result.push_str(&external_repr);
+        }
+        Ok(result)
+    }
+
+    /// Load the tasks in other projects which are referred to by external_refs, and get reprs for
+    /// those matching the status flag. As in `view_deps_action`, the status flag defaults to
+    /// "available", i.e. tasks which are not done in their own project's workflow.
+    fn get_external_dep_reprs(
+        external_refs: Vec<&ExternalRef>,
+        opt_status_flag: Option<String>,
+    ) -> Result<Vec<String>, String> {
+        let status_filter = match opt_status_flag.as_deref() {
+            None | Some("all") => None,
+            Some(status_flag) => Some(TaskStatus::from_name(status_flag)),
+        };
+        let mut loaded: HashMap<&str, Option<Project>> = HashMap::new();
+        let mut reprs = Vec::new();
+        for external_ref in external_refs {
+            let other = loaded.entry(&external_ref.project)
+                .or_insert_with(|| Project::load(&external_ref.project).ok());
+            let found = other.as_ref().and_then(|other| {
+                let tree = other.get_tree();
+                tree.get_task(&external_ref.task_id)
+                    .map(|task| (task, tree.is_done(&external_ref.task_id)))
+            });
+            let (task, done) = match found {
+                Some(found) => found,
+                None => {
+                    reprs.push(format!("[?] {}: (missing)", external_ref));
+                    continue;
+                },
+            };
+            let status = task.get_status();
+            let matches = match (&status_filter, &opt_status_flag) {
+                (Some(status_filter), _) => status == status_filter,
+                (None, None) => !done,
+                (None, Some(_)) => true,
+            };
+            if matches {
+                reprs.push(format!("{} {}: {}", status, external_ref, task.get_name()));
+            }
+        }
+        Ok(reprs)
+    }
+
+    /// Draw the given task (or, if no task is given, every root task of the active project) and its
+    /// dependencies as a tree. Optionally limits the depth of the tree and filters it by status.
+    fn tree_action(&self, session: &mut Session) -> Result<String, String> {
+        let proj = Self::load_active_project_resolved(session)?;
+        let opt_task_id = match self.args.first() {
+            Some(arg) => Some(Self::parse_as_task_id(&proj, arg)?),
+            None => None,
+        };
+        let max_depth = match self.flags.get("--depth") {
+            Some(depth) => match depth.parse() {
+                Ok(depth) => Some(depth),
+                _ => return Err("depth must be a non-negative integer.".to_string()),
+            },
+            None => None,
+        };
+
+        let rendered = render::render_tree(
+            proj.get_tree(),
+            opt_task_id.as_ref(),
+            max_depth,
+            self.flags.get("--status").cloned(),
+        )?;
+        if rendered.is_empty() {
+            return Err(format!("no matching tasks in project {}", bold_text(proj.get_name())));
+        }
+        Ok(rendered)
+    }
+
+    /// Export the active project's dependency graph (or the subgraph under the given task) as a
+    /// Graphviz DOT or Mermaid diagram. The format defaults to DOT.
+    fn export_action(&self, session: &mut Session) -> Result<String, String> {
+        let proj = Self::load_active_project(session)?;
+        let opt_task_id = match self.args.first() {
+            Some(arg) => Some(Self::parse_as_task_id(&proj, arg)?),
+            None => None,
+        };
+        let format = match self.flags.get("--format") {
+            Some(format_flag) => render::ExportFormat::from_format_flag(format_flag)?,
+            None => render::ExportFormat::Dot,
+        };
+        render::export_graph(proj.get_tree(), opt_task_id.as_ref(), format)
+    }
+
+    /// Report closed tasks in the active project which depend on tasks that aren't closed.
+    fn check_action(&self, session: &mut Session) -> Result<String, String> {
+        let proj = Self::load_active_project(session)?;
+        let tree = proj.get_tree();
+        let inconsistencies = tree.find_inconsistencies();
+        if inconsistencies.is_empty() {
+            return Ok(format!("No inconsistencies in project {}.", bold_text(proj.get_name())));
+        }
+
+        let mut result = format!(
+            "closed tasks with unfinished dependencies in project {}:",
+            bold_text(proj.get_name()),
+        );
+        for (task_id, dep_id) in inconsistencies {
+            result.push_str(&format!(
+                "\n{}\n    depends on {}",
+                tree.get_task_repr(&task_id).unwrap(),
+                tree.get_task_repr(&dep_id).unwrap(),
+            ));
+        }
         Ok(result)
     }
 
-    /// Print the prompt and get user input while the user's input is not in `allowed_vals`.
-    fn get_user_input(prompt: &str, allowed_vals: Vec<&str>) -> String {
+    /// View the active project's reopen policy, or set it if a policy is given.
+    fn reopen_policy_action(&self, session: &mut Session) -> Result<String, String> {
+        let mut proj = Self::load_active_project(session)?;
+        let policy_flag = match self.parse_optional_argument(0) {
+            None => return Ok(format!(
+                "Reopen policy for project {} is {}.",
+                bold_text(proj.get_name()),
+                bold_text(proj.get_tree().get_reopen_policy().to_name()),
+            )),
+            Some(policy_flag) => policy_flag,
+        };
+
+        let reopen_policy = ReopenPolicy::from_policy_flag(&policy_flag)?;
+        proj.get_tree_mut().set_reopen_policy(reopen_policy);
+        session.save(&mut proj)?;
+        Ok(format!(
+            "Set reopen policy for project {} to {}.",
+            bold_text(proj.get_name()),
+            bold_text(reopen_policy.to_name()),
+        ))
+    }
+
+    /// Move (or copy) the task with the given id from the active project into the project given by
+    /// "--to". With "--with-deps", the task's dependencies are carried along as well; when moving,
+    /// dependencies which other tasks in the active project still need are copied instead.
+    fn transfer_task_action(
+        &self,
+        session: &mut Session,
+        is_move: bool,
+    ) -> Result<String, String> {
+        let target_name = self.flags.get("--to").unwrap();
+        let with_deps = self.flags.contains_key("--with-deps");
+
+        let mut proj = Self::load_active_project(session)?;
+        let task_id = Self::parse_as_task_id(&proj, &self.args[0])?;
+        if target_name == proj.get_name() {
+            return Err(format!("Task {} is already in project {}.",
+                bold_tid(task_id),
+                bold_text(target_name),
+            ));
+        }
+        if !Project::exists(target_name)? {
+            return Err(format!("There is no project named {}.", target_name));
+        }
+        let mut target = Project::load(target_name)?;
+
+        let source_name = proj.get_name().to_string();
+        let target_tree = target.get_tree_mut();
+        let id_map = match is_move {
+            true => proj.get_tree_mut()
+                .move_subgraph_to(&task_id, with_deps, target_tree, &source_name, target_name)?,
+            false => proj.get_tree()
+                .copy_subgraph_to(&task_id, with_deps, target_tree, &source_name)?,
+        };
+        session.save(&mut target)?;
+        if is_move {
+            session.save(&mut proj)?;
+        }
+
+        let external_refs = proj.get_tree().get_all_external_refs();
+        let moved_ref = |task_id| ExternalRef { project: target_name.clone(), task_id };
+        let mut old_ids: Vec<&TID> = id_map.keys().collect();
+        old_ids.sort();
+        let mut result = format!(
+            "{} {} tasks to project {}:",
+            if is_move { "Moved" } else { "Copied" },
+            id_map.len(),
+            bold_text(target_name),
+        );
+        for old_id in old_ids {
+            result.push_str(&format!(
+                "\ntask {} is now task {}",
+                bold_tid(*old_id),
+                bold_tid(id_map[old_id]),
+            ));
+            if is_move && proj.get_tree().get_task(old_id).is_some() {
+                result.push_str(" (copied, since other tasks depend on it)");
+            } else if is_move && external_refs.contains(&moved_ref(id_map[old_id])) {
+                result.push_str(&format!(
+                    " (tasks in project {} now depend on it there)",
+                    bold_text(proj.get_name()),
+                ));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Merge the task with drop_id into the task with keep_id, after asking the user to confirm
+    /// ("y"/"n"). The kept task takes over the dropped task's dependencies, dependents and
+    /// description, and the dropped task is removed.
+    fn merge_tasks_action(&self, session: &mut Session) -> Result<String, String> {
+        let mut proj = Self::load_active_project(session)?;
+        let keep_id = Self::parse_as_task_id(&proj, &self.args[0])?;
+        let drop_id = Self::parse_as_task_id(&proj, &self.args[1])?;
+        let tasks = proj.get_tree_mut();
+        let mut reprs = Vec::new();
+        for task_id in [keep_id, drop_id] {
+            match tasks.get_task_repr(&task_id) {
+                Some(task_repr) => reprs.push(task_repr),
+                None => return Err(format!(
+                    "There is no task for the active project with id {}.",
+                    task_id
+                )),
+            }
+        }
+
+        let prompt = format!(
+            "Are you sure you want to merge the task '{}' into '{}' and remove it (y/n)? ",
+            reprs[1],
+            reprs[0],
+        );
+        if !self.confirm(&prompt)? {
+            return Ok(format!("Did not merge task {}.", bold_tid(drop_id)));
+        }
+        tasks.merge_tasks(&keep_id, &drop_id)?;
+        session.save(&mut proj)?;
+        Ok(format!("Merged task {} into task {}.", bold_tid(drop_id), bold_tid(keep_id)))
+    }
+
+    /// Replace the task with the given id by new tasks with the given names, each of which takes
+    /// over the original task's dependencies and dependents.
+    fn split_task_action(&self, session: &mut Session) -> Result<String, String> {
+        let names = self.args[1..].to_vec();
+        let mut proj = Self::load_active_project(session)?;
+        let task_id = Self::parse_as_task_id(&proj, &self.args[0])?;
+        let new_ids = proj.get_tree_mut().split_task(&task_id, names)?;
+        session.save(&mut proj)?;
+
+        let new_ids: Vec<String> = new_ids.into_iter().map(bold_tid).collect();
+        Ok(format!("Split task {} into tasks {}.", bold_tid(task_id), new_ids.join(", ")))
+    }
+
+    /// List the active project's redundant dependencies, i.e. dependencies which are already
+    /// implied by other dependencies. With "--apply", removes them.
+    fn lint_deps_action(&self, session: &mut Session) -> Result<String, String> {
+        let mut proj = Self::load_active_project(session)?;
+        let apply = self.flags.contains_key("--apply");
+        let redundant = match apply {
+            true => proj.get_tree_mut().remove_redundant_dependencies()?,
+            false => proj.get_tree().find_redundant_dependencies(),
+        };
+        if redundant.is_empty() {
+            return Ok(format!(
+                "No redundant dependencies in project {}.",
+                bold_text(proj.get_name()),
+            ));
+        }
+        if apply {
+            session.save(&mut proj)?;
+        }
+
+        let mut result = format!(
+            "{} redundant dependencies in project {}:",
+            if apply { "Removed" } else { "Found" },
+            bold_text(proj.get_name()),
+        );
+        for (task_id, dep_id) in redundant {
+            result.push_str(&format!(
+                "\ntask {} on task {}",
+                bold_tid(task_id),
+                bold_tid(dep_id),
+            ));
+        }
+        if !apply {
+            result.push_str("\nRun \"tasktree lint-deps --apply\" to remove them.");
+        }
+        Ok(result)
+    }
+
+    /// View or edit the active project's workflow: its statuses, which of them count as done,
+    /// and which status changes are allowed.
+    fn workflow_action(&self, session: &mut Session) -> Result<String, String> {
+        let mut proj = Self::load_active_project(session)?;
+        let subcommand = match self.subcommand {
+            None => return Ok(Self::format_workflow(&proj)),
+            Some(subcommand) => subcommand,
+        };
+
+        let name = &self.args[0];
+        let msg = match subcommand {
+            "add-state" => {
+                proj.get_tree_mut().get_workflow_mut().add_state(
+                    name,
+                    self.flags.contains_key("--done"),
+                    self.flags.contains_key("--needs-deps-done"),
+                )?;
+                format!("Added status {}.", bold_text(name))
+            },
+            "rm-state" => {
+                proj.get_tree_mut().remove_workflow_state(name)?;
+                format!("Removed status {}.", bold_text(name))
+            },
+            "set-transitions" if self.args.len() == 1 => {
+                proj.get_tree_mut().get_workflow_mut().set_transitions(name, None)?;
+                format!("Tasks with status {} can now be set to any status.", bold_text(name))
+            },
+            "set-transitions" => {
+                let to = self.args[1..].to_vec();
+                proj.get_tree_mut().get_workflow_mut().set_transitions(name, Some(to.clone()))?;
+                format!(
+                    "Tasks with status {} can now be set to: {}.",
+                    bold_text(name),
+                    to.join(", "),
+                )
+            },
+            _ => panic!("Unknown workflow subcommand {}", subcommand),
+        };
+        session.save(&mut proj)?;
+        Ok(msg)
+    }
+
+    /// Report lead time, cycle time, weekly throughput (for the last 4 weeks, or as many as given
+    /// by "--weeks") and aging in-progress tasks for the active project.
+    fn stats_action(&self, session: &mut Session) -> Result<String, String> {
+        let weeks = match self.flags.get("--weeks") {
+            Some(weeks) => match weeks.parse::<usize>() {
+                Ok(weeks) if weeks > 0 => weeks,
+                _ => return Err(format!("Invalid number of weeks {}.", bold_text(weeks))),
+            },
+            None => 4,
+        };
+
+        let proj = Self::load_active_project(session)?;
+        let project_stats = stats::compute_stats(proj.get_tree(), chrono::Utc::now(), weeks);
+        Ok(format!(
+            "{}\n{}",
+            bold_text(&underline_text(&format!("stats for project {}:", proj.get_name()))),
+            stats::format_stats(proj.get_tree(), &project_stats),
+        ))
+    }
+
+    /// Report how far along the given task is, i.e. how many of its (transitive) dependencies have
+    /// each status. Without a task, reports on every task in the active project.
+    fn progress_action(&self, session: &mut Session) -> Result<String, String> {
+        let proj = Self::load_active_project(session)?;
+        let tree = proj.get_tree();
+        let (title, task_ids) = match self.parse_optional_argument(0) {
+            None => (format!("progress for project {}:", proj.get_name()), tree.get_task_ids()),
+            Some(arg) => {
+                let task_id = Self::parse_as_task_id(&proj, &arg)?;
+                let dep_ids: Vec<TID> = tree.get_dependencies(&task_id, Some("all".to_string()))?
+                    .into_iter()
+                    .copied()
+                    .collect();
+                if dep_ids.is_empty() {
+                    return Ok(format!("Task {} has no dependencies.", bold_tid(task_id)));
+                }
+                let name = tree.get_task(&task_id).unwrap().get_name();
+                (format!("progress for task {}: {}", task_id, name), dep_ids)
+            },
+        };
+        Ok(format!(
+            "{}\n{}",
+            bold_text(&underline_text(&title)),
+            stats::format_progress(tree, &task_ids),
+        ))
+    }
+
+    /// Show how many tasks were open and closed over time, per day (the default) or per week, as a
+    /// chart (the default), CSV or JSON. Starts at "--since" (by default, when the oldest of the
+    /// tasks was created). If a task is given, only counts its (transitive) dependencies.
+    fn burndown_action(&self, session: &mut Session) -> Result<String, String> {
+        let bucket = match self.flags.get("--bucket") {
+            Some(bucket_flag) => stats::Bucket::from_bucket_flag(bucket_flag)?,
+            None => stats::Bucket::Day,
+        };
+        let format = match self.flags.get("--format") {
+            Some(format_flag) => stats::BurndownFormat::from_format_flag(format_flag)?,
+            None => stats::BurndownFormat::Chart,
+        };
+
+        let proj = Self::load_active_project(session)?;
+        let tree = proj.get_tree();
+        let task_ids: Vec<TID> = match self.args.first() {
+            None => tree.get_task_ids(),
+            Some(arg) => tree.get_dependencies(
+                &Self::parse_as_task_id(&proj, arg)?,
+                Some("all".to_string()),
+            )?.into_iter().copied().collect(),
+        };
+        let since = match self.flags.get("--since") {
+            Some(since) => match chrono::NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+                Ok(since) => since,
+                Err(_) => return Err(format!("Invalid date {}; use YYYY-MM-DD.", bold_text(since))),
+            },
+            None => match task_ids.iter()
+                .map(|task_id| tree.get_task(task_id).unwrap().get_created_timestamp())
+                .min()
+            {
+                Some(oldest) => oldest.with_timezone(&chrono::Local).naive_local().date(),
+                None => return Err("There are no tasks to show a burndown for.".to_string()),
+            },
+        };
+
+        let points = stats::compute_burndown(tree, &task_ids, since, bucket, chrono::Utc::now());
+        Ok(stats::format_burndown(&points, format))
+    }
+
+    /// Show the help for the given action (or one of its subcommands). Without an action, lists
+    /// every action.
+    fn help_action(&self) -> Result<String, String> {
+        let name = match self.args.first() {
+            Some(name) => name,
+            None => {
+                let mut result = format!("{}\n\n{}", GENERAL_USAGE, bold_text("actions:"));
+                for spec in COMMANDS {
+                    let prefix = format!("\n  {:<15} ", spec.name);
+                    result.push_str(&args::wrap(&prefix, spec.summary, 101));
+                }
+                result.push_str(&format!("\n\n{}", bold_text("aliases:")));
+                config::with_active(|settings| {
+                    for (alias, command_line) in settings.get_aliases() {
+                        result.push_str(&format!("\n  {:<15} {}", alias, command_line));
+                    }
+                });
+                return Ok(result);
+            },
+        };
+        if Self::get_spec(name).is_none() {
+            let alias = config::with_active(|settings| {
+                settings.get_alias(name).map(str::to_string)
+            });
+            if let Some(command_line) = alias {
+                return Ok(format!("{} is an alias for \"{}\".", name, command_line));
+            }
+        }
+        Action::from_cmdline_arg(name)?;
+        let spec = Self::get_spec(name).unwrap();
+        let subcommand = match self.args.get(1) {
+            Some(subcommand) => Some(args::find_subcommand(spec, subcommand)?),
+            None => None,
+        };
+        Ok(args::help(spec, subcommand))
+    }
+
+    /// Run a batch script from the given file, or from stdin.
+    fn batch_action(&self, session: &mut Session) -> Result<String, String> {
+        let script = match self.parse_optional_argument(0) {
+            Some(path) => match fs::read_to_string(&path) {
+                Ok(script) => script,
+                Err(_) => return Err(format!("Could not read {}.", path)),
+            },
+            None => {
+                let mut script = String::new();
+                if io::stdin().read_to_string(&mut script).is_err() {
+                    return Err("Could not read the script from stdin.".to_string());
+                }
+                script
+            },
+        };
+        Self::load_active_project(session)?;
+        batch::run(&script, session)
+    }
+
+    /// List the settings for the active project (or the project given by "--project"), or get,
+    /// set or unset one of them. Settings are set for every project unless a project is given.
+    /// Settings can be unset even if the configuration file is invalid, to fix it.
+    fn config_action(&self) -> Result<String, String> {
+        let project_name = self.flags.get("--project").map(String::as_str);
+        if let Some(project_name) = project_name {
+            if !Project::exists(project_name)? {
+                return Err(format!("There is no project named {}.", project_name));
+            }
+        }
+        let scope = match project_name {
+            Some(project_name) => format!(" for project {}", bold_text(project_name)),
+            None => String::new(),
+        };
+        let mut config = match Config::load() {
+            Ok(config) => config,
+            Err(_) if self.subcommand == Some("unset") => {
+                let key = &self.args[0];
+                return match Config::unset_in_file(project_name, key)? {
+                    true => Ok(format!("Unset {}{}.", bold_text(key), scope)),
+                    false => Ok(format!("{} isn't set{}.", bold_text(key), scope)),
+                };
+            },
+            Err(msg) => return Err(msg),
+        };
+        let shown_name = project_name.map(str::to_string).or_else(Project::get_active);
+        let subcommand = match self.subcommand {
+            None => return Ok(Self::format_config(&config, shown_name.as_deref())),
+            Some(subcommand) => subcommand,
+        };
+
+        let key = &self.args[0];
+        match subcommand {
+            "get" => match config.get_settings(shown_name.as_deref()).get(key)? {
+                Some(value) => Ok(value),
+                None => Ok(format!("{} isn't set.", bold_text(key))),
+            },
+            "set" => {
+                let value = &self.args[1];
+                if let Some(name) = key.strip_prefix("alias.") {
+                    if Self::get_spec(name).is_some() {
+                        return Err(format!("{} is an action, so it can't be an alias.", name));
+                    }
+                }
+                config.set(project_name, key, Some(value))?;
+                config.save()?;
+                Ok(format!("Set {} to {}{}.", bold_text(key), bold_text(value), scope))
+            },
+            "unset" => {
+                if !config.set(project_name, key, None)? {
+                    return Ok(format!("{} isn't set{}.", bold_text(key), scope));
+                }
+                config.save()?;
+                Ok(format!("Unset {}{}.", bold_text(key), scope))
+            },
+            _ => panic!("Unknown config subcommand {}", subcommand),
+        }
+    }
+
+    /// List the settings for the given project, marking the ones set only for the project.
+    fn format_config(config: &Config, project_name: Option<&str>) -> String {
+        let pairs = config.get_settings(project_name).get_pairs();
+        if pairs.is_empty() {
+            return format!("No settings are set in {}.", Config::get_path());
+        }
+        let overrides = config.get_scope(project_name).get_pairs();
+        let title = match project_name {
+            Some(project_name) => format!("settings for project {}:", project_name),
+            None => "settings:".to_string(),
+        };
+        let mut result = bold_text(&underline_text(&title));
+        for (key, value) in pairs {
+            result.push_str(&format!("\n{} = {}", key, value));
+            let is_override = overrides.iter().any(|(override_key, _)| *override_key == key);
+            if project_name.is_some() && is_override {
+                result.push_str(" (for this project)");
+            }
+        }
+        result
+    }
+
+    /// Print the completion script for the given shell.
+    fn completions_action(&self) -> Result<String, String> {
+        Ok(Shell::from_shell_flag(&self.args[0])?.get_script().to_string())
+    }
+
+    /// Print the completions for the last of the given words, one per line.
+    fn complete_action(&self) -> Result<String, String> {
+        Ok(completion::format_candidates(&Self::complete(&self.args)))
+    }
+
+    /// Describe the statuses and allowed status changes of a project's workflow.
+    fn format_workflow(proj: &Project) -> String {
+        let mut result = bold_text(&underline_text(
+            &format!("workflow for project {}:", proj.get_name()),
+        ));
+        for state in proj.get_tree().get_workflow().get_states() {
+            let status = TaskStatus::from_name(state.get_name());
+            let mut properties = Vec::new();
+            if state.is_done() {
+                properties.push("done");
+            }
+            if state.needs_deps_done() {
+                properties.push("needs deps done");
+            }
+            let workflow = proj.get_tree().get_workflow();
+            let transitions = match workflow.get_transitions(state.get_name()) {
+                None => "any".to_string(),
+                Some(to) if to.is_empty() => "none".to_string(),
+                Some(to) => to.join(", "),
+            };
+            result.push_str(&format!(
+                "\n{} {}{} -> {}",
+                status,
+                bold_text(state.get_name()),
+                match properties.is_empty() {
+                    true => String::new(),
+                    false => format!(" ({})", properties.join(", ")),
+                },
+                transitions,
+            ));
+        }
+        result
+    }
+
+    /// Ask the user to confirm ("y"/"n") something. With "--yes", confirms without asking. With
+    /// "--no-input", or if stdin isn't a terminal (e.g. in scripts), fails instead of asking.
+    fn confirm(&self, prompt: &str) -> Result<bool, String> {
+        let yes = self.flags.contains_key("--yes");
+        let no_input = self.flags.contains_key("--no-input");
+        if yes && no_input {
+            return Err("--yes and --no-input can't be used together.".to_string());
+        } else if yes {
+            return Ok(true);
+        } else if no_input {
+            return Err("Confirmation needed, but --no-input was given. Use --yes to confirm."
+                .to_string());
+        }
+        match config::with_active(|settings| settings.confirm) {
+            Some(ConfirmMode::Yes) => return Ok(true),
+            Some(ConfirmMode::NoInput) => return Err(
+                "Confirmation needed, but the \"confirm\" setting is no-input. Use --yes to \
+                 confirm.".to_string()
+            ),
+            _ => (),
+        }
+        if !io::stdin().is_terminal() {
+            return Err("Confirmation needed, but stdin isn't a terminal. Use --yes to confirm."
+                .to_string());
+        }
+        Ok(Self::get_user_input(prompt, vec!["y", "n"])? == "y")
+    }
+
+    /// Let the user edit the given text in their editor, and return the edited text.
+    fn edit_text(text: &str) -> Result<String, String> {
+        let editor = config::with_active(|settings| settings.editor.clone())
+            .or_else(|| env::var("VISUAL").ok())
+            .or_else(|| env::var("EDITOR").ok())
+            .unwrap_or_else(|| "vi".to_string());
+        // Only ever write to a file created here, so that an existing file or symlink at the
+        // (predictable) path isn't overwritten.
+        let mut attempt = 0;
+        let (path, mut file) = loop {
+            let path = env::temp_dir()
+                .join(format!("tasktree-edit-{}-{}.txt", process::id(), attempt));
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => {
+                    attempt += 1;
+                },
+                Err(_) => return Err("Could not create the file to edit.".to_string()),
+            }
+        };
+        let written = file.write_all(text.as_bytes());
+        drop(file);
+        if written.is_err() {
+            let _ = fs::remove_file(&path);
+            return Err("Could not write the file to edit.".to_string());
+        }
+        // Run the editor through the shell, so that it can be given with arguments.
+        let status = process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&path)
+            .status();
+        let edited = fs::read_to_string(&path);
+        let _ = fs::remove_file(&path);
+        match status {
+            Ok(status) if status.success() => (),
+            _ => return Err(format!("The editor ({}) failed.", editor)),
+        }
+        edited.map_err(|_| "Could not read the edited file.".to_string())
+    }
+
+    /// Whether lists of tasks are output as JSON, per the "output-format" setting.
+    fn is_json_output() -> bool {
+        config::with_active(|settings| settings.output_format == Some(OutputFormat::Json))
+    }
+
+    /// Prompt the user until they enter one of the allowed values. Fails if stdin ends first.
+    fn get_user_input(prompt: &str, allowed_vals: Vec<&str>) -> Result<String, String> {
         let mut input;
         loop {
             print!("{}", prompt);
             io::stdout().flush().unwrap();
             input = String::new();
-            io::stdin().read_line(&mut input)
-                .expect("Error getting user input");
+            match io::stdin().read_line(&mut input) {
+                Ok(0) => return Err("No answer given (end of input).".to_string()),
+                Ok(_) => (),
+                Err(_) => return Err("Could not read the answer.".to_string()),
+            }
             input = input.trim().to_string();
             if allowed_vals.contains(&&input[..]) {
                 break;
             }
         }
-        input
+        Ok(input)
     }
 
-    fn parse_as_task_id(arg: &str) -> Result<TID, String> {
-        match arg.parse() {
-            Ok(result) => Ok(result),
-            _ => return Err("task_id must be a positive integer.".to_string()),
-        }
-        
+    /// Resolve a task argument in the given project; see `TaskTree::resolve_task`.
+    fn parse_as_task_id(proj: &Project, arg: &str) -> Result<TID, String> {
+        proj.get_tree().resolve_task(arg)
     }
 
-    fn load_active_project() -> Result<Project, String> {
-        let active = Project::get_active();
-        match active {
-            None => return Err(NO_ACTIVE_MSG.to_string()),
-            Some(active_name) => {
-                if !Project::exists(&active_name)? {
-                    return Err(NO_ACTIVE_MSG.to_string());
-                } else {
-                    return Ok(Project::load(&active_name)?);
-                }
-            }
+    /// Parse a dependency argument, which is either a task in the given project or a
+    /// "project:task_id" reference to a task in another project.
+    fn parse_as_dependency(proj: &Project, arg: &str) -> Result<DependencyArg, String> {
+        match ExternalRef::parse(arg) {
+            Some(external_ref) => Ok(DependencyArg::External(external_ref)),
+            None => Ok(DependencyArg::Local(Self::parse_as_task_id(proj, arg)?)),
         }
     }
 
-    fn check_args_len(&self, args_len: usize, usage: &str) -> Result<(), String> {
-        match self.args.len() >= args_len {
-            false => return Err(usage.to_string()),
-            _ => Ok(()),
-        }
+    fn load_active_project(session: &mut Session) -> Result<Project, String> {
+        session.load_active_project()?.ok_or_else(|| NO_ACTIVE_MSG.to_string())
+    }
+
+    /// Load the active project along with whether the tasks it depends on in other projects are
+    /// done, for commands which look at which tasks are available.
+    fn load_active_project_resolved(session: &mut Session) -> Result<Project, String> {
+        let mut proj = Self::load_active_project(session)?;
+        proj.resolve_external_statuses();
+        Ok(proj)
     }
 
     fn parse_optional_argument(&self, idx: usize) -> Option<String> {
-        if &self.args.len() < &(idx+1) {
+        if self.args.len() < (idx+1) {
             None
         } else {
             Some(self.args[idx].clone())
@@ -528,13 +1626,150 @@ impl Command {
 }
 
 pub fn bold_text(text: &str) -> String {
+    if !is_color_enabled() {
+        return text.to_string();
+    }
     format!("{}", Style::new().bold().paint(text))
 }
 
 pub fn underline_text(text: &str) -> String {
+    if !is_color_enabled() {
+        return text.to_string();
+    }
     format!("{}", Style::new().underline().paint(text))
 }
 
 pub fn bold_tid(tid: TID) -> String {
     bold_text(&tid.to_string())
 }
+
+/// Get a status's marker, e.g. "[O]" for open. With the rich theme, the marker is colored by
+/// status.
+pub fn status_marker(status: &TaskStatus) -> String {
+    let marker = status.to_string();
+    let is_rich = config::with_active(|settings| settings.theme == Some(Theme::Rich));
+    if !is_rich || !is_color_enabled() {
+        return marker;
+    }
+    let colour = match status {
+        TaskStatus::Open => Colour::Blue,
+        TaskStatus::InProgress => Colour::Yellow,
+        TaskStatus::Closed => Colour::Green,
+        TaskStatus::Cancelled => Colour::Fixed(8),
+        TaskStatus::Custom(_) => Colour::Cyan,
+    };
+    format!("{}", colour.paint(marker))
+}
+
+/// Get a task's repr for output, with its status marker styled by `status_marker`.
+pub fn styled_repr(task: &Task) -> String {
+    let marker_len = task.get_status().to_string().len();
+    format!("{}{}", status_marker(task.get_status()), &task.get_repr()[marker_len..])
+}
+
+/// Whether output is styled, per the "color" setting. By default, this is decided by
+/// `is_auto_color_enabled`.
+fn is_color_enabled() -> bool {
+    static AUTO_COLOR: OnceLock<bool> = OnceLock::new();
+    match config::with_active(|settings| settings.color) {
+        Some(ColorMode::Always) => true,
+        Some(ColorMode::Never) => false,
+        Some(ColorMode::Auto) | None => *AUTO_COLOR.get_or_init(|| is_auto_color_enabled(
+            env::var("NO_COLOR").ok(),
+            env::var("CLICOLOR_FORCE").ok(),
+            io::stdout().is_terminal(),
+        )),
+    }
+}
+
+/// Whether output is styled in the auto color mode, given the NO_COLOR and CLICOLOR_FORCE
+/// environment variables: only when output goes to a terminal, unless NO_COLOR is set, or whenever
+/// CLICOLOR_FORCE is set (to anything but "0"). Empty variables count as unset.
+fn is_auto_color_enabled(
+    no_color: Option<String>,
+    clicolor_force: Option<String>,
+    is_terminal: bool,
+) -> bool {
+    let is_set = |var: &Option<String>| var.as_ref().is_some_and(|value| !value.is_empty());
+    if is_set(&no_color) {
+        false
+    } else if is_set(&clicolor_force) && clicolor_force.as_deref() != Some("0") {
+        true
+    } else {
+        is_terminal
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_every_command_has_an_action() {
+        for spec in COMMANDS {
+            assert!(Action::from_cmdline_arg(spec.name).is_ok(), "no action for {}", spec.name);
+        }
+    }
+
+    #[test]
+    fn test_confirm_flags() {
+        let command = Command::from_args(to_args(&["rm", "3", "--yes"])).unwrap();
+        assert_eq!(command.confirm("Remove (y/n)? "), Ok(true));
+        let command = Command::from_args(to_args(&["rm", "3", "--no-input"])).unwrap();
+        assert!(command.confirm("Remove (y/n)? ").unwrap_err().contains("--no-input"));
+        let command = Command::from_args(to_args(&["merge", "1", "2", "--yes", "--no-input"]));
+        assert!(command.unwrap().confirm("Merge (y/n)? ").is_err());
+        assert!(Command::from_args(to_args(&["view", "--yes"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args() {
+        let command = Command::from_args(to_args(&["set", "3", "--cascade", "closed"])).unwrap();
+        assert_eq!(command.action, Action::SetStatus);
+        assert_eq!(command.args, to_args(&["3", "closed"]));
+        assert!(command.flags.contains_key("--cascade"));
+
+        let command = Command::from_args(to_args(&["workflow", "rm-state", "-h"])).unwrap();
+        assert_eq!(command.action, Action::Help);
+        assert_eq!(command.args, to_args(&["workflow", "rm-state"]));
+        let help = command.help_action().unwrap();
+        assert!(help.starts_with("usage: tasktree workflow rm-state name"));
+
+        assert!(Command::from_args(to_args(&["view-task", "1", "2"])).is_err());
+        let err = Action::from_cmdline_arg("lint-dep").err().unwrap();
+        assert!(err.contains("did you mean \"lint-deps\"?"));
+    }
+
+    #[test]
+    fn test_auto_color() {
+        let set = |value: &str| Some(value.to_string());
+        assert!(is_auto_color_enabled(None, None, true));
+        assert!(!is_auto_color_enabled(None, None, false));
+        assert!(!is_auto_color_enabled(set("1"), None, true));
+        assert!(is_auto_color_enabled(set(""), None, true));
+        assert!(is_auto_color_enabled(None, set("1"), false));
+        assert!(!is_auto_color_enabled(None, set("0"), false));
+        assert!(!is_auto_color_enabled(set("1"), set("1"), true));
+    }
+
+    #[test]
+    fn test_default_alias() {
+        let mut settings = Settings::new();
+        let args = Command::expand_alias_with(to_args(&["cancel", "3", "--cascade"]), &settings)
+            .unwrap();
+        let command = Command::from_args(args).unwrap();
+        assert_eq!(command.action, Action::SetStatus);
+        assert_eq!(command.args, to_args(&["3", "cancelled"]));
+        assert!(command.flags.contains_key("--cascade"));
+        assert!(Command::expand_alias_with(to_args(&["cancel"]), &settings).is_err());
+
+        settings.set("alias.cancel", Some("rm {1}")).unwrap();
+        let args = Command::expand_alias_with(to_args(&["cancel", "3"]), &settings).unwrap();
+        assert_eq!(args, to_args(&["rm", "3"]));
+    }
+}
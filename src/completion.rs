@@ -0,0 +1,381 @@
+use crate::args::{ Arity, CommandSpec, FlagSpec, ValueKind };
+use crate::project::Project;
+use crate::workflow::Workflow;
+
+
+const BASH_SCRIPT: &str = r#"_tasktree() {
+    local line="${COMP_LINE:0:COMP_POINT}"
+    local -a words
+    read -ra words <<< "$line"
+    if [[ -z $line || $line == *[[:space:]] ]]; then
+        words+=("")
+    fi
+    local cur="${words[${#words[@]}-1]}"
+    local IFS=$'\n'
+    COMPREPLY=($(tasktree __complete "${words[@]:1}" 2>/dev/null | cut -f1))
+    # Bash treats ":" as a word break, so only the part after the last ":" is replaced.
+    if [[ $cur == *:* && $COMP_WORDBREAKS == *:* ]]; then
+        COMPREPLY=("${COMPREPLY[@]#"${cur%:*}:"}")
+    fi
+}
+complete -F _tasktree tasktree"#;
+
+const ZSH_SCRIPT: &str = r#"_tasktree() {
+    local -a lines candidates
+    local line value
+    lines=("${(@f)$(tasktree __complete "${(@)words[2,CURRENT]}" 2>/dev/null)}")
+    for line in $lines; do
+        [[ -z $line ]] && continue
+        value="${${line%%$'\t'*}//:/\\:}"
+        if [[ $line == *$'\t'* ]]; then
+            candidates+=("$value:${line#*$'\t'}")
+        else
+            candidates+=("$value")
+        fi
+    done
+    _describe tasktree candidates
+}
+compdef _tasktree tasktree"#;
+
+const FISH_SCRIPT: &str = r#"function __tasktree_complete
+    set -l tokens (commandline -opc) (commandline -ct)
+    tasktree __complete $tokens[2..-1] 2>/dev/null
+end
+complete -c tasktree -f -a '(__tasktree_complete)'"#;
+
+
+/// A shell which completion scripts can be printed for.
+#[derive(PartialEq, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+
+    pub fn from_shell_flag(shell_flag: &str) -> Result<Self, String> {
+        match shell_flag {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            _ => Err(format!("Invalid shell {}. Use bash, zsh or fish.", shell_flag)),
+        }
+    }
+
+    /// The completion script for this shell. The scripts call "tasktree __complete" with the words
+    /// typed so far (without the program name, and including the word being completed), which
+    /// prints the candidates one per line, with an optional description after a tab.
+    pub fn get_script(&self) -> &'static str {
+        match self {
+            Self::Bash => BASH_SCRIPT,
+            Self::Zsh => ZSH_SCRIPT,
+            Self::Fish => FISH_SCRIPT,
+        }
+    }
+
+}
+
+
+/// What the word being completed is.
+#[derive(PartialEq, Debug)]
+enum Target {
+    Actions,
+    Subcommands(&'static CommandSpec),
+    /// The command's flags which weren't given yet.
+    Flags(&'static CommandSpec, Vec<String>),
+    /// A value of an argument or flag with the given name and kind.
+    Value(&'static str, ValueKind),
+    Nothing,
+}
+
+/// A possible completion, with a description to show next to it.
+#[derive(PartialEq, Debug)]
+pub struct Candidate {
+    pub value: String,
+    pub description: String,
+}
+
+impl Candidate {
+
+    fn new(value: &str, description: &str) -> Self {
+        Candidate { value: value.to_string(), description: description.to_string() }
+    }
+
+}
+
+
+/// Complete the last of the given words, which are a command line (without the program name) as
+/// far as it has been typed. Only candidates starting with the last word are returned.
+pub fn complete(commands: &'static [CommandSpec], words: &[String]) -> Vec<Candidate> {
+    let current = match words.last() {
+        Some(current) => current,
+        None => return Vec::new(),
+    };
+    let candidates = match find_target(commands, words) {
+        Target::Actions => commands.iter()
+            .map(|spec| Candidate::new(spec.name, spec.summary))
+            .collect(),
+        Target::Subcommands(spec) => spec.subcommands.iter()
+            .map(|subcommand| Candidate::new(subcommand.name, subcommand.summary))
+            .collect(),
+        Target::Flags(spec, given) => spec.flags.iter()
+            .filter(|flag| !given.iter().any(|name| name == flag.name))
+            .map(|flag| Candidate::new(flag.name, flag.help))
+            .chain(std::iter::once(Candidate::new("--help", "Show the help for the action.")))
+            .collect(),
+        Target::Value(name, kind) => complete_value(commands, name, kind, current),
+        Target::Nothing => Vec::new(),
+    };
+    candidates.into_iter()
+        .filter(|candidate| candidate.value.starts_with(&current[..]))
+        .collect()
+}
+
+/// Format candidates for the completion scripts: one per line, with the description (if any)
+/// after a tab.
+pub fn format_candidates(candidates: &[Candidate]) -> String {
+    let lines: Vec<String> = candidates.iter()
+        .map(|candidate| {
+            let description = candidate.description.replace(['\t', '\n'], " ");
+            if description.is_empty() {
+                candidate.value.clone()
+            } else {
+                format!("{}\t{}", candidate.value, description)
+            }
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Work out what the last word is from the words before it.
+fn find_target(commands: &'static [CommandSpec], words: &[String]) -> Target {
+    let (current, before) = match words.split_last() {
+        Some((current, before)) if !before.is_empty() => (current, before),
+        _ => return Target::Actions,
+    };
+    let mut spec = match commands.iter().find(|spec| spec.name == before[0]) {
+        Some(spec) => spec,
+        None => return Target::Nothing,
+    };
+    let mut rest = &before[1..];
+    if !spec.subcommands.is_empty() {
+        match rest.first() {
+            None if current.starts_with('-') => return Target::Flags(spec, Vec::new()),
+            None => return Target::Subcommands(spec),
+            Some(first) => match spec.subcommands.iter().find(|sub| sub.name == first) {
+                Some(subcommand) => spec = subcommand,
+                None => return Target::Nothing,
+            },
+        }
+        rest = &rest[1..];
+    }
+
+    let mut given_flags = Vec::new();
+    let mut num_positional = 0;
+    let mut only_positional = false;
+    let mut awaiting_value: Option<&FlagSpec> = None;
+    for word in rest {
+        if let Some(flag) = awaiting_value.take() {
+            given_flags.push(flag.name.to_string());
+        } else if only_positional || !word.starts_with('-') {
+            num_positional += 1;
+        } else if word == "--" {
+            only_positional = true;
+        } else {
+            let name = word.split('=').next().unwrap();
+            match spec.flags.iter().find(|flag| flag.name == name) {
+                Some(flag) if flag.value.is_some() && !word.contains('=') => {
+                    awaiting_value = Some(flag);
+                },
+                _ => given_flags.push(name.to_string()),
+            }
+        }
+    }
+
+    if let Some(flag) = awaiting_value {
+        return Target::Value(flag.value.unwrap(), flag.kind);
+    }
+    if !only_positional && current.starts_with('-') {
+        return Target::Flags(spec, given_flags);
+    }
+    let arg = match spec.args.get(num_positional) {
+        Some(arg) => arg,
+        None => match spec.args.last() {
+            Some(last) if matches!(last.arity, Arity::Repeated { .. }) => last,
+            _ => return Target::Nothing,
+        },
+    };
+    Target::Value(arg.name, arg.kind)
+}
+
+/// Get the possible values of an argument or flag. Projects, tasks and statuses are loaded from
+/// the saved projects; without an active project, there are no tasks and the statuses are the
+/// default ones.
+fn complete_value(
+    commands: &'static [CommandSpec],
+    name: &str,
+    kind: ValueKind,
+    current: &str,
+) -> Vec<Candidate> {
+    match kind {
+        ValueKind::Text if name.contains('|') => name.split('|')
+            .map(|choice| Candidate::new(choice, ""))
+            .collect(),
+        ValueKind::Text => Vec::new(),
+        ValueKind::Action => commands.iter()
+            .map(|spec| Candidate::new(spec.name, spec.summary))
+            .collect(),
+        ValueKind::Project => {
+            let mut names = Project::get_project_names().unwrap_or_default();
+            names.sort();
+            names.iter().map(|name| Candidate::new(name, "")).collect()
+        },
+        ValueKind::Task => match load_active_project() {
+            Some(proj) => complete_task_ids(&proj, ""),
+            None => Vec::new(),
+        },
+        ValueKind::Dependency => match current.split_once(':') {
+            Some((project_name, _)) => match Project::load(project_name) {
+                Ok(proj) => complete_task_ids(&proj, &format!("{}:", project_name)),
+                Err(_) => Vec::new(),
+            },
+            None => match load_active_project() {
+                Some(proj) => complete_task_ids(&proj, ""),
+                None => Vec::new(),
+            },
+        },
+        ValueKind::Status(extra) => {
+            let proj = load_active_project();
+            let default_workflow = Workflow::default();
+            let workflow = match &proj {
+                Some(proj) => proj.get_tree().get_workflow(),
+                None => &default_workflow,
+            };
+            workflow.get_states().iter()
+                .map(|state| state.get_name())
+                .chain(extra.iter().copied())
+                .map(|name| Candidate::new(name, ""))
+                .collect()
+        },
+    }
+}
+
+/// Get a project's task IDs, described by the tasks' names.
+fn complete_task_ids(proj: &Project, prefix: &str) -> Vec<Candidate> {
+    let tree = proj.get_tree();
+    tree.get_task_ids().iter()
+        .map(|task_id| Candidate::new(
+            &format!("{}{}", prefix, task_id),
+            tree.get_task(task_id).unwrap().get_name(),
+        ))
+        .collect()
+}
+
+fn load_active_project() -> Option<Project> {
+    Project::load(&Project::get_active()?).ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::ArgSpec;
+
+    static COMMANDS: &[CommandSpec] = &[
+        CommandSpec {
+            name: "export",
+            summary: "Export the graph.",
+            args: &[ArgSpec { name: "task_id", arity: Arity::Optional, kind: ValueKind::Task }],
+            flags: &[
+                FlagSpec {
+                    name: "--format",
+                    value: Some("dot|mermaid"),
+                    required: false,
+                    help: "The format.",
+                    kind: ValueKind::Text,
+                },
+                FlagSpec {
+                    name: "--all",
+                    value: None,
+                    required: false,
+                    help: "Export all.",
+                    kind: ValueKind::Text,
+                },
+            ],
+            subcommands: &[],
+        },
+        CommandSpec {
+            name: "workflow",
+            summary: "Edit the workflow.",
+            args: &[],
+            flags: &[],
+            subcommands: &[CommandSpec {
+                name: "set-transitions",
+                summary: "Set transitions.",
+                args: &[
+                    ArgSpec { name: "from", arity: Arity::Required, kind: ValueKind::Text },
+                    ArgSpec {
+                        name: "to",
+                        arity: Arity::Repeated { min: 0 },
+                        kind: ValueKind::Status(&[]),
+                    },
+                ],
+                flags: &[],
+                subcommands: &[],
+            }],
+        },
+    ];
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn values(candidates: Vec<Candidate>) -> Vec<String> {
+        candidates.into_iter().map(|candidate| candidate.value).collect()
+    }
+
+    #[test]
+    fn test_find_target() {
+        let export = &COMMANDS[0];
+        let workflow = &COMMANDS[1];
+        assert_eq!(find_target(COMMANDS, &to_args(&["ex"])), Target::Actions);
+        assert_eq!(find_target(COMMANDS, &to_args(&["nope", ""])), Target::Nothing);
+        assert_eq!(
+            find_target(COMMANDS, &to_args(&["export", ""])),
+            Target::Value("task_id", ValueKind::Task),
+        );
+        assert_eq!(
+            find_target(COMMANDS, &to_args(&["export", "--format", ""])),
+            Target::Value("dot|mermaid", ValueKind::Text),
+        );
+        assert_eq!(
+            find_target(COMMANDS, &to_args(&["export", "--format", "dot", "3", "-"])),
+            Target::Flags(export, to_args(&["--format"])),
+        );
+        assert_eq!(find_target(COMMANDS, &to_args(&["export", "3", ""])), Target::Nothing);
+        assert_eq!(
+            find_target(COMMANDS, &to_args(&["workflow", "s"])),
+            Target::Subcommands(workflow),
+        );
+        assert_eq!(
+            find_target(COMMANDS, &to_args(&["workflow", "set-transitions", "open", "x", ""])),
+            Target::Value("to", ValueKind::Status(&[])),
+        );
+    }
+
+    #[test]
+    fn test_complete() {
+        assert_eq!(values(complete(COMMANDS, &to_args(&["w"]))), to_args(&["workflow"]));
+        assert_eq!(
+            values(complete(COMMANDS, &to_args(&["export", "--format", ""]))),
+            to_args(&["dot", "mermaid"]),
+        );
+        assert_eq!(
+            values(complete(COMMANDS, &to_args(&["export", "--all", "--"]))),
+            to_args(&["--format", "--help"]),
+        );
+        let candidates = complete(COMMANDS, &to_args(&["workflow", ""]));
+        assert_eq!(format_candidates(&candidates), "set-transitions\tSet transitions.");
+    }
+}
@@ -0,0 +1,543 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::{ Once, RwLock };
+use serde::{ Deserialize, Serialize };
+use crate::args;
+use crate::project::Project;
+use crate::timestamp;
+
+
+const CONFIG_FILE: &str = "config.json";
+const ALIAS_PREFIX: &str = "alias.";
+
+/// The settings, with what they do, in the order they are listed.
+pub const KEYS: &[(&str, &str)] = &[
+    ("default-status", "The status which \"view\" lists without one (by default, available)."),
+    ("output-format", "How \"view\" and \"find\" list tasks: text (the default) or json."),
+    ("color", "Whether output is styled: auto (the default), always or never. With auto, output \
+               is styled when it goes to a terminal, unless NO_COLOR is set, or when \
+               CLICOLOR_FORCE is set."),
+    ("theme", "plain (the default), or rich to also color status markers by status."),
+    ("date-format", "The strftime format for timestamps (by default, %Y-%m-%d %H:%M). The \
+                     TASKTREE_DATE_FORMAT environment variable overrides it."),
+    ("confirm", "What actions which need confirmation do without --yes or --no-input: ask (the \
+                 default), yes or no-input."),
+    ("editor", "The command which \"edit\" opens descriptions with (by default, $VISUAL or \
+                $EDITOR)."),
+    ("alias.NAME", "A command line which NAME stands for."),
+];
+
+/// The aliases which are there unless they are set to something else.
+pub const DEFAULT_ALIASES: &[(&str, &str)] = &[
+    ("start", "set {1} in-progress"),
+    ("done", "set {1} closed"),
+    ("reopen", "set {1} open"),
+    ("cancel", "set {1} cancelled"),
+    ("all", "view all"),
+];
+
+/// The settings of the command being run.
+static ACTIVE_SETTINGS: RwLock<Settings> = RwLock::new(Settings::new());
+/// Makes sure that an invalid configuration file is only warned about once.
+static INVALID_WARNING: Once = Once::new();
+
+
+/// Settings, either for every project or overriding them for one project. Unset settings use
+/// their defaults.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Settings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<OutputFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<ColorMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<Theme>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirm: Option<ConfirmMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Settings {
+
+    pub const fn new() -> Self {
+        Settings {
+            default_status: None,
+            output_format: None,
+            color: None,
+            theme: None,
+            date_format: None,
+            confirm: None,
+            editor: None,
+            aliases: BTreeMap::new(),
+        }
+    }
+
+    /// Get a setting's value, or None if it isn't set. Aliases which aren't set are looked up in
+    /// the default aliases.
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        check_key(key)?;
+        Ok(match key {
+            "default-status" => self.default_status.clone(),
+            "output-format" => self.output_format.map(|format| format.to_name().to_string()),
+            "color" => self.color.map(|color| color.to_name().to_string()),
+            "theme" => self.theme.map(|theme| theme.to_name().to_string()),
+            "date-format" => self.date_format.clone(),
+            "confirm" => self.confirm.map(|confirm| confirm.to_name().to_string()),
+            "editor" => self.editor.clone(),
+            _ => self.get_alias(&key[ALIAS_PREFIX.len()..]).map(str::to_string),
+        })
+    }
+
+    /// Get the command line which an alias stands for, if there is such an alias.
+    pub fn get_alias(&self, name: &str) -> Option<&str> {
+        match self.aliases.get(name) {
+            Some(command_line) => Some(command_line),
+            None => DEFAULT_ALIASES.iter()
+                .find(|(default_name, _)| *default_name == name)
+                .map(|(_, command_line)| *command_line),
+        }
+    }
+
+    /// Get every alias, including the default ones, as (name, command line) pairs sorted by name.
+    pub fn get_aliases(&self) -> Vec<(&str, &str)> {
+        let mut aliases: BTreeMap<&str, &str> = DEFAULT_ALIASES.iter().copied().collect();
+        for (name, command_line) in &self.aliases {
+            aliases.insert(name, command_line);
+        }
+        aliases.into_iter().collect()
+    }
+
+    /// Set a setting, or unset it if no value is given. Returns whether it was set before.
+    pub fn set(&mut self, key: &str, value: Option<&str>) -> Result<bool, String> {
+        check_key(key)?;
+        let was_set = match key.strip_prefix(ALIAS_PREFIX) {
+            Some(name) => self.aliases.contains_key(name),
+            None => self.get(key)?.is_some(),
+        };
+        if value.is_some_and(|value| value.trim().is_empty()) {
+            return Err(format!("The value for {} is empty.", key));
+        }
+        let value = value.map(str::to_string);
+        match key {
+            "default-status" => self.default_status = value,
+            "output-format" => {
+                self.output_format = value.as_deref().map(OutputFormat::from_name).transpose()?;
+            },
+            "color" => self.color = value.as_deref().map(ColorMode::from_name).transpose()?,
+            "theme" => self.theme = value.as_deref().map(Theme::from_name).transpose()?,
+            "date-format" => match value {
+                Some(date_format) if !timestamp::is_valid_format(&date_format) => {
+                    return Err(format!("Invalid date format {}.", date_format));
+                },
+                date_format => self.date_format = date_format,
+            },
+            "confirm" => {
+                self.confirm = value.as_deref().map(ConfirmMode::from_name).transpose()?;
+            },
+            "editor" => self.editor = value,
+            _ => {
+                let name = key[ALIAS_PREFIX.len()..].to_string();
+                match value {
+                    Some(command_line) => self.aliases.insert(name, command_line),
+                    None => self.aliases.remove(&name),
+                };
+            },
+        }
+        Ok(was_set)
+    }
+
+    /// Get every setting which is set, as (key, value) pairs in the order of `KEYS`.
+    pub fn get_pairs(&self) -> Vec<(String, String)> {
+        let mut pairs = Vec::new();
+        for (key, _) in KEYS.iter().filter(|(key, _)| !key.starts_with(ALIAS_PREFIX)) {
+            if let Some(value) = self.get(key).unwrap() {
+                pairs.push((key.to_string(), value));
+            }
+        }
+        for (name, command_line) in &self.aliases {
+            pairs.push((format!("{}{}", ALIAS_PREFIX, name), command_line.clone()));
+        }
+        pairs
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Settings::new()
+    }
+
+    /// Get these settings with the ones which the overrides set replaced.
+    fn with_overrides(&self, overrides: &Settings) -> Settings {
+        let mut aliases = self.aliases.clone();
+        aliases.extend(overrides.aliases.clone());
+        Settings {
+            default_status: overrides.default_status.clone().or(self.default_status.clone()),
+            output_format: overrides.output_format.or(self.output_format),
+            color: overrides.color.or(self.color),
+            theme: overrides.theme.or(self.theme),
+            date_format: overrides.date_format.clone().or(self.date_format.clone()),
+            confirm: overrides.confirm.or(self.confirm),
+            editor: overrides.editor.clone().or(self.editor.clone()),
+            aliases,
+        }
+    }
+
+}
+
+
+/// How lists of tasks are output.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Invalid output format {}; use text or json.", name)),
+        }
+    }
+
+    fn to_name(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        }
+    }
+
+}
+
+
+/// Whether output is styled.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMode {
+    /// Style output which goes to a terminal, following the NO_COLOR and CLICOLOR_FORCE
+    /// environment variables.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(format!("Invalid color mode {}; use auto, always or never.", name)),
+        }
+    }
+
+    fn to_name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Always => "always",
+            Self::Never => "never",
+        }
+    }
+
+}
+
+
+/// How styled output looks.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// Only bold and underlined text.
+    Plain,
+    /// Also color status markers by status.
+    Rich,
+}
+
+impl Theme {
+
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "plain" => Ok(Self::Plain),
+            "rich" => Ok(Self::Rich),
+            _ => Err(format!("Invalid theme {}; use plain or rich.", name)),
+        }
+    }
+
+    fn to_name(self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::Rich => "rich",
+        }
+    }
+
+}
+
+
+/// What actions which need confirmation do when neither --yes nor --no-input is given.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfirmMode {
+    Ask,
+    Yes,
+    NoInput,
+}
+
+impl ConfirmMode {
+
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "ask" => Ok(Self::Ask),
+            "yes" => Ok(Self::Yes),
+            "no-input" => Ok(Self::NoInput),
+            _ => Err(format!("Invalid confirmation mode {}; use ask, yes or no-input.", name)),
+        }
+    }
+
+    fn to_name(self) -> &'static str {
+        match self {
+            Self::Ask => "ask",
+            Self::Yes => "yes",
+            Self::NoInput => "no-input",
+        }
+    }
+
+}
+
+
+/// The configuration file: the settings for every project, and the settings which override them
+/// for single projects.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct Config {
+    #[serde(flatten)]
+    global: Settings,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    projects: BTreeMap<String, Settings>,
+}
+
+impl Config {
+
+    /// Load the configuration file. If there is none yet, nothing is set.
+    pub fn load() -> Result<Self, String> {
+        let path = Self::get_path();
+        let read_str = match fs::read_to_string(&path) {
+            Ok(read_str) => read_str,
+            Err(_) => return Ok(Config::default()),
+        };
+        serde_json::from_str(&read_str)
+            .map_err(|err| format!("Invalid configuration file {}: {}", path, err))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        Self::write(&serde_json::to_string_pretty(self).unwrap())
+    }
+
+    /// Unset a setting in the configuration file without reading the other settings, so that a
+    /// setting with an invalid value can still be removed. Returns whether it was set.
+    pub fn unset_in_file(project_name: Option<&str>, key: &str) -> Result<bool, String> {
+        check_key(key)?;
+        let path = Self::get_path();
+        let read_str = match fs::read_to_string(&path) {
+            Ok(read_str) => read_str,
+            Err(_) => return Ok(false),
+        };
+        let mut value: serde_json::Value = serde_json::from_str(&read_str)
+            .map_err(|err| format!("Invalid configuration file {}: {}", path, err))?;
+        if !unset_in_json(&mut value, project_name, key) {
+            return Ok(false);
+        }
+        Self::write(&serde_json::to_string_pretty(&value).unwrap())?;
+        Ok(true)
+    }
+
+    fn write(serialized: &str) -> Result<(), String> {
+        let path = Self::get_path();
+        match fs::write(&path, format!("{}\n", serialized)) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("Could not save the configuration file {}.", path)),
+        }
+    }
+
+    pub fn get_path() -> String {
+        Project::get_data_path(CONFIG_FILE)
+    }
+
+    /// Get the settings for the given project (or for every project), including the ones which
+    /// are set for every project.
+    pub fn get_settings(&self, project_name: Option<&str>) -> Settings {
+        match project_name.and_then(|project_name| self.projects.get(project_name)) {
+            Some(overrides) => self.global.with_overrides(overrides),
+            None => self.global.clone(),
+        }
+    }
+
+    /// Get the settings which are set for the given project, or for every project.
+    pub fn get_scope(&self, project_name: Option<&str>) -> Settings {
+        match project_name {
+            Some(project_name) => self.projects.get(project_name).cloned().unwrap_or_default(),
+            None => self.global.clone(),
+        }
+    }
+
+    /// Set (or unset, if no value is given) a setting for the given project, or for every project.
+    /// Returns whether it was set before.
+    pub fn set(
+        &mut self,
+        project_name: Option<&str>,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<bool, String> {
+        let project_name = match project_name {
+            None => return self.global.set(key, value),
+            Some(project_name) => project_name.to_string(),
+        };
+        let settings = self.projects.entry(project_name.clone()).or_default();
+        let was_set = settings.set(key, value)?;
+        if settings.is_empty() {
+            self.projects.remove(&project_name);
+        }
+        Ok(was_set)
+    }
+
+}
+
+
+/// Load the settings for the given project (or for every project). If the configuration file is
+/// invalid, warns about it and uses the default settings, so that commands still run.
+pub fn load_settings(project_name: Option<&str>) -> Settings {
+    match Config::load() {
+        Ok(config) => config.get_settings(project_name),
+        Err(msg) => {
+            INVALID_WARNING.call_once(|| {
+                eprintln!("Warning: {}. Using the default settings.", msg);
+            });
+            Settings::default()
+        },
+    }
+}
+
+/// Use the given settings for the rest of the command.
+pub fn use_settings(settings: Settings) {
+    *ACTIVE_SETTINGS.write().unwrap() = settings;
+}
+
+/// Look at the settings of the command being run.
+pub fn with_active<T>(f: impl FnOnce(&Settings) -> T) -> T {
+    f(&ACTIVE_SETTINGS.read().unwrap())
+}
+
+/// Remove a setting from a parsed configuration file. Returns whether it was there.
+fn unset_in_json(value: &mut serde_json::Value, project_name: Option<&str>, key: &str) -> bool {
+    let mut scope = Some(value);
+    if let Some(project_name) = project_name {
+        scope = scope.and_then(|scope| scope.get_mut("projects"))
+            .and_then(|projects| projects.get_mut(project_name));
+    }
+    let (scope, name) = match key.strip_prefix(ALIAS_PREFIX) {
+        Some(name) => (scope.and_then(|scope| scope.get_mut("aliases")), name),
+        None => (scope, key),
+    };
+    scope.and_then(serde_json::Value::as_object_mut)
+        .and_then(|settings| settings.remove(name))
+        .is_some()
+}
+
+fn check_key(key: &str) -> Result<(), String> {
+    if let Some(name) = key.strip_prefix(ALIAS_PREFIX) {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("Invalid alias name \"{}\".", name));
+        }
+        return Ok(());
+    }
+    let keys: Vec<&str> = KEYS.iter()
+        .map(|(key, _)| *key)
+        .filter(|key| !key.starts_with(ALIAS_PREFIX))
+        .collect();
+    if !keys.contains(&key) {
+        return Err(format!("no setting \"{}\"{}", key, args::did_you_mean(key, &keys)));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_get() {
+        let mut settings = Settings::new();
+        assert_eq!(settings.set("color", Some("never")), Ok(false));
+        assert_eq!(settings.set("color", Some("never")), Ok(true));
+        assert_eq!(settings.get("color"), Ok(Some("never".to_string())));
+        assert_eq!(settings.set("alias.done", Some("set {1} closed")), Ok(false));
+        assert_eq!(settings.get("alias.done"), Ok(Some("set {1} closed".to_string())));
+        assert!(settings.set("color", Some("sometimes")).is_err());
+        assert_eq!(settings.set("theme", Some("rich")), Ok(false));
+        assert_eq!(settings.set("theme", None), Ok(true));
+        assert!(settings.set("date-format", Some("%Q")).is_err());
+        assert!(settings.set("colour", Some("never")).is_err());
+        assert!(settings.set("alias.", Some("view")).is_err());
+        assert!(settings.set("editor", Some(" ")).is_err());
+        assert_eq!(settings.get_pairs(), vec![
+            ("color".to_string(), "never".to_string()),
+            ("alias.done".to_string(), "set {1} closed".to_string()),
+        ]);
+
+        assert_eq!(settings.set("color", None), Ok(true));
+        assert_eq!(settings.set("alias.done", None), Ok(true));
+        assert!(settings.is_empty());
+        assert_eq!(settings.get("alias.done"), Ok(Some("set {1} closed".to_string())));
+        assert_eq!(settings.set("alias.done", None), Ok(false));
+        settings.set("alias.all", Some("view all --tree")).unwrap();
+        assert_eq!(settings.get_alias("all"), Some("view all --tree"));
+        assert_eq!(settings.get_alias("start"), Some("set {1} in-progress"));
+        assert_eq!(settings.get_alias("nope"), None);
+    }
+
+    #[test]
+    fn test_project_overrides() {
+        let mut config = Config::default();
+        config.set(None, "color", Some("never")).unwrap();
+        config.set(None, "confirm", Some("no-input")).unwrap();
+        config.set(Some("work"), "confirm", Some("yes")).unwrap();
+
+        let settings = config.get_settings(Some("work"));
+        assert_eq!(settings.color, Some(ColorMode::Never));
+        assert_eq!(settings.confirm, Some(ConfirmMode::Yes));
+        assert_eq!(config.get_settings(Some("home")).confirm, Some(ConfirmMode::NoInput));
+        assert_eq!(config.get_settings(None).confirm, Some(ConfirmMode::NoInput));
+
+        let serialized = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"color":"never","confirm":"no-input","projects":{"work":{"confirm":"yes"}}}"#,
+        );
+        assert_eq!(serde_json::from_str::<Config>(&serialized).unwrap(), config);
+
+        config.set(Some("work"), "confirm", None).unwrap();
+        assert!(config.projects.is_empty());
+    }
+
+    #[test]
+    fn test_unset_invalid_setting() {
+        let mut value: serde_json::Value = serde_json::from_str(
+            r#"{"color":"sometimes","aliases":{"x":"view"},"projects":{"work":{"theme":"dark"}}}"#,
+        ).unwrap();
+        assert!(serde_json::from_value::<Config>(value.clone()).is_err());
+        assert!(unset_in_json(&mut value, None, "color"));
+        assert!(!unset_in_json(&mut value, None, "color"));
+        assert!(unset_in_json(&mut value, None, "alias.x"));
+        assert!(!unset_in_json(&mut value, None, "theme"));
+        assert!(unset_in_json(&mut value, Some("work"), "theme"));
+        assert!(serde_json::from_value::<Config>(value).is_ok());
+    }
+}
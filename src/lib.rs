@@ -1,3 +1,14 @@
 pub mod tree;
 pub mod project;
 pub mod command;
+pub mod args;
+pub mod completion;
+pub mod session;
+pub mod config;
+pub mod shell;
+pub mod batch;
+pub mod tui;
+pub mod render;
+pub mod workflow;
+pub mod stats;
+pub mod timestamp;
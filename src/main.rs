@@ -1,4 +1,5 @@
 use tasktree::command::{ Command, GENERAL_USAGE };
+use tasktree::session::Session;
 use std::{ env, process };
 
 fn main() {
@@ -12,8 +13,9 @@ fn main() {
     match command {
         Err(msg) => println!("{}", msg),
         Ok(cmd) => {
-            match cmd.execute() {
+            match cmd.execute(&mut Session::new()) {
                 Err(msg) => println!("Error: {}", msg),
+                Ok(response) if response.is_empty() => (),
                 Ok(response) => println!("{}", response),
             }
         }
@@ -1,4 +1,7 @@
-use crate::tree::TaskTree;
+use crate::tree::{ ExternalRef, TaskTree, TID };
+use crate::command::{ bold_text, bold_tid };
+use crate::timestamp;
+use std::collections::{ HashMap, HashSet };
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{fs, io::Write};
@@ -9,31 +12,30 @@ const FILE_EXTENSION: &str = ".json";
 const TASKTREE_DIR: &str = ".tasktree/";
 const ACTIVE_PROJ: &str = "active";
 const PROJECTS_DIR: &str = "projects/";
-pub const DATE_FORMAT: &str = "%m-%d-%Y %H:%M";
 
 
 /// Struct representing a tasktree project.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Project {
     tasks: TaskTree,
     name: String,
     desc: String,
-    created_timestamp: String,
-    modified_timestamp: String,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    created_timestamp: DateTime<Utc>,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    modified_timestamp: DateTime<Utc>,
 }
 
 impl Project {
 
     pub fn new(name: String, desc: String) -> Self {
         let cur_time: DateTime<Utc> = Utc::now();
-        let created_timestamp = cur_time.format(DATE_FORMAT).to_string();
-        let modified_timestamp = String::new();
         let mut proj = Project {
             tasks: TaskTree::new(),
             name,
             desc,
-            created_timestamp,
-            modified_timestamp,
+            created_timestamp: cur_time,
+            modified_timestamp: cur_time,
         };
         proj.save().unwrap();
         proj
@@ -56,13 +58,11 @@ impl Project {
             _ => return Err(err_msg.to_string()),
         };
         for file in files {
-            let filename = String::from(
-                format!("{}", file.unwrap().path().display())
-                    .split("/")
-                    .last()
-                    .unwrap()
-                    .replace(FILE_EXTENSION, ""),
-            );
+            let filename = format!("{}", file.unwrap().path().display())
+                .split('/')
+                .next_back()
+                .unwrap()
+                .replace(FILE_EXTENSION, "");
             proj_names.push(filename);
         }
         Ok(proj_names)
@@ -74,13 +74,13 @@ impl Project {
     }
 
     /// Get the time at which this tasktree project was created.
-    pub fn get_created_timestamp(&self) -> &str {
+    pub fn get_created_timestamp(&self) -> &DateTime<Utc> {
         &self.created_timestamp
     }
 
     /// Get the time at which this tasktree project was last modified.
-    pub fn get_modified_timestamp(&self) -> &str {
-        &&self.modified_timestamp
+    pub fn get_modified_timestamp(&self) -> &DateTime<Utc> {
+        &self.modified_timestamp
     }
 
     /// Get this project's name.
@@ -105,8 +105,7 @@ impl Project {
 
     /// Save this project.
     pub fn save(&mut self) -> Result<(), String> {
-        let cur_time: DateTime<Utc> = Utc::now();
-        self.modified_timestamp = cur_time.format(DATE_FORMAT).to_string();
+        self.modified_timestamp = Utc::now();
 
         let project_path = Self::get_project_path(&self.name);
         if Self::exists(&self.name)? {
@@ -118,6 +117,7 @@ impl Project {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(true)
             .open(&project_path);
 
 
@@ -135,7 +135,8 @@ impl Project {
         }
     }
 
-    /// Load a project.
+    /// Load a project. The other projects which it depends on aren't loaded; see
+    /// `resolve_external_statuses`.
     pub fn load(name: &str) -> Result<Self, String> {
         let project_path = Self::get_project_path(name);
         let err_msg = format!("Could not load project {}.", name);
@@ -147,6 +148,110 @@ impl Project {
         Ok(serde_json::from_str(read_str.trim()).unwrap())
     }
 
+    /// Load the projects which this project's tasks depend on, and record whether the referenced
+    /// tasks are done in this project's tree. This is needed before anything which depends on
+    /// whether tasks are available. References to missing projects or tasks are skipped.
+    pub fn resolve_external_statuses(&mut self) {
+        let mut loaded: HashMap<String, Option<Project>> = HashMap::new();
+        let external_refs: Vec<ExternalRef> = self.tasks.get_all_external_refs()
+            .into_iter()
+            .cloned()
+            .collect();
+        for external_ref in external_refs {
+            let other = loaded.entry(external_ref.project.clone())
+                .or_insert_with(|| Self::load(&external_ref.project).ok());
+            let done = other.as_ref()
+                .filter(|other| other.get_tree().get_task(&external_ref.task_id).is_some())
+                .map(|other| other.get_tree().is_done(&external_ref.task_id));
+            if let Some(done) = done {
+                self.tasks.set_external_done(external_ref, done);
+            }
+        }
+    }
+
+    /// Add the task with depends_on_id as a dependency for the task with task_id, checking that
+    /// this doesn't create a cycle through other projects.
+    pub fn add_dependency(&mut self, task_id: &TID, depends_on_id: &TID) -> Result<(), String> {
+        let from = ExternalRef { project: self.name.clone(), task_id: *depends_on_id };
+        self.check_no_cross_project_cycle(task_id, &from)?;
+        self.tasks.add_dependency(task_id, depends_on_id)
+    }
+
+    /// Add the task in another project referred to by depends_on as a dependency for the task with
+    /// task_id. Requires that the other task exists and that this doesn't create a cycle.
+    pub fn add_external_dependency(
+        &mut self,
+        task_id: &TID,
+        depends_on: ExternalRef,
+    ) -> Result<(), String> {
+        if depends_on.project == self.name {
+            return Err(format!(
+                "Task {} is in project {}; refer to it by its id.",
+                bold_text(&depends_on.to_string()),
+                bold_text(&self.name),
+            ));
+        }
+        if !Self::exists(&depends_on.project)? {
+            return Err(format!("There is no project named {}.", depends_on.project));
+        }
+        let other = Self::load(&depends_on.project)?;
+        let done = match other.get_tree().get_task(&depends_on.task_id) {
+            Some(_) => other.get_tree().is_done(&depends_on.task_id),
+            None => return Err(format!(
+                "Task {} does not exist in project {}.",
+                bold_tid(depends_on.task_id),
+                bold_text(&depends_on.project),
+            )),
+        };
+        self.check_no_cross_project_cycle(task_id, &depends_on)?;
+        self.tasks.add_external_dependency(task_id, depends_on.clone())?;
+        self.tasks.set_external_done(depends_on, done);
+        Ok(())
+    }
+
+    /// Check that making the task with task_id depend on `from` doesn't create a cycle, i.e. that
+    /// the task can't be reached from `from` by following dependencies across projects.
+    fn check_no_cross_project_cycle(
+        &self,
+        task_id: &TID,
+        from: &ExternalRef,
+    ) -> Result<(), String> {
+        let to = ExternalRef { project: self.name.clone(), task_id: *task_id };
+        let mut loaded: HashMap<String, Option<Project>> = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut to_visit = vec![from.clone()];
+        while let Some(visit) = to_visit.pop() {
+            if visit == to {
+                return Err(format!(
+                    "Adding dependency for task {} on task {} creates a cycle.",
+                    bold_tid(*task_id),
+                    bold_text(&from.to_string()),
+                ));
+            }
+            if !visited.insert(visit.clone()) {
+                continue;
+            }
+            let tree = if visit.project == self.name {
+                &self.tasks
+            } else {
+                let other = loaded.entry(visit.project.clone())
+                    .or_insert_with(|| Self::load(&visit.project).ok());
+                match other {
+                    Some(other) => other.get_tree(),
+                    None => continue,
+                }
+            };
+            if let Some(children) = tree.get_children(&visit.task_id) {
+                for child_id in children {
+                    let project = visit.project.clone();
+                    to_visit.push(ExternalRef { project, task_id: *child_id });
+                }
+            }
+            to_visit.extend(tree.get_external_children(&visit.task_id).iter().cloned());
+        }
+        Ok(())
+    }
+
     /// Delete a project.
     pub fn remove(name: &str) -> Result<(), String> {
         let proj_path = Self::get_project_path(name);
@@ -165,6 +270,7 @@ impl Project {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(true)
             .open(Self::get_active_path())
             .unwrap();
         file.set_len(0).unwrap();
@@ -181,8 +287,13 @@ impl Project {
     }
 
     fn get_active_path() -> String {
+        Self::get_data_path(ACTIVE_PROJ)
+    }
+
+    /// Get the path of a file in the directory where tasktree keeps its data.
+    pub fn get_data_path(file_name: &str) -> String {
         let home: &str = env!("HOME");
-        format!("{}/{}{}", home, TASKTREE_DIR, ACTIVE_PROJ)
+        format!("{}/{}{}", home, TASKTREE_DIR, file_name)
     }
 
     fn get_project_path(project_name: &str) -> String {
@@ -209,8 +320,8 @@ mod tests {
             tasks: tasktree,
             name: TEST_PROJ.to_string(),
             desc: "desc".to_string(),
-            created_timestamp: "asdf".to_string(),
-            modified_timestamp: "sdfg".to_string(),
+            created_timestamp: Utc::now(),
+            modified_timestamp: Utc::now(),
         };
 
         project.save().unwrap();
@@ -227,4 +338,59 @@ mod tests {
         assert!(!Project::get_project_names().unwrap().contains(&TEST_PROJ.to_string()));
         assert!(!Project::exists(TEST_PROJ).unwrap());
     }
+
+    #[test]
+    fn cross_project_dependency_test() {
+        let mut backend = Project::new("test_backend".to_string(), "desc".to_string());
+        let mut frontend = Project::new("test_frontend".to_string(), "desc".to_string());
+        let api = backend.get_tree_mut().add_task("API".to_string(), None);
+        let ui = frontend.get_tree_mut().add_task("UI".to_string(), None);
+        backend.save().unwrap();
+
+        let api_ref = ExternalRef { project: "test_backend".to_string(), task_id: api };
+        frontend.add_external_dependency(&ui, api_ref.clone()).unwrap();
+        frontend.save().unwrap();
+        let mut loaded = Project::load("test_frontend").unwrap();
+        assert!(loaded.get_tree().is_available(&ui));
+        loaded.resolve_external_statuses();
+        assert!(!loaded.get_tree().is_available(&ui));
+
+        // The backend task can't depend back on the frontend task.
+        let ui_ref = ExternalRef { project: "test_frontend".to_string(), task_id: ui };
+        assert!(backend.add_external_dependency(&api, ui_ref).is_err());
+        let missing_ref = ExternalRef { project: "test_backend".to_string(), task_id: 42 };
+        assert!(frontend.add_external_dependency(&ui, missing_ref).is_err());
+
+        backend.get_tree_mut().set_status(&api, "closed".to_string()).unwrap();
+        backend.save().unwrap();
+        let mut loaded = Project::load("test_frontend").unwrap();
+        loaded.resolve_external_statuses();
+        assert!(loaded.get_tree().is_available(&ui));
+
+        Project::remove("test_backend").unwrap();
+        Project::remove("test_frontend").unwrap();
+    }
+
+    #[test]
+    fn custom_done_external_dependency_test() {
+        let mut backend = Project::new("test_custom_backend".to_string(), "desc".to_string());
+        let mut frontend = Project::new("test_custom_frontend".to_string(), "desc".to_string());
+        backend.get_tree_mut().get_workflow_mut().add_state("wont-fix", true, false).unwrap();
+        let api = backend.get_tree_mut().add_task("API".to_string(), None);
+        let ui = frontend.get_tree_mut().add_task("UI".to_string(), None);
+        backend.get_tree_mut().set_status(&api, "wont-fix".to_string()).unwrap();
+        backend.save().unwrap();
+
+        // The backend's workflow decides that its task is done.
+        let api_ref = ExternalRef { project: "test_custom_backend".to_string(), task_id: api };
+        frontend.add_external_dependency(&ui, api_ref).unwrap();
+        assert!(frontend.get_tree().is_available(&ui));
+        frontend.save().unwrap();
+        let mut loaded = Project::load("test_custom_frontend").unwrap();
+        loaded.resolve_external_statuses();
+        assert!(loaded.get_tree().is_available(&ui));
+
+        Project::remove("test_custom_backend").unwrap();
+        Project::remove("test_custom_frontend").unwrap();
+    }
 }
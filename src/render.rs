@@ -0,0 +1,408 @@
+use std::collections::{ HashMap, HashSet };
+use crate::tree::{ TaskStatus, TaskTree, TID };
+use crate::command::{ bold_text, bold_tid, status_marker };
+
+
+const BRANCH: &str = "├── ";
+const LAST_BRANCH: &str = "└── ";
+const PIPE: &str = "│   ";
+const BLANK: &str = "    ";
+const SHARED_MARKER: &str = " (*)";
+
+
+/// Render a task and its dependencies as an indented tree. If no task is given, renders every root
+/// task (i.e. every task that no other task depends on). Tasks reachable through more than one
+/// path are only expanded the first time they are drawn; later occurrences are marked with "(*)".
+///
+/// `max_depth` limits how many levels of dependencies are drawn below each root. `status_flag`
+/// may be a status, "available" or "all" (the default); tasks which don't match it are only drawn
+/// if one of their dependencies does.
+pub fn render_tree(
+    tree: &TaskTree,
+    task_id: Option<&TID>,
+    max_depth: Option<usize>,
+    status_flag: Option<String>,
+) -> Result<String, String> {
+    let filter = StatusFilter::from_opt_status_flag(tree, status_flag)?;
+    let root_ids = match task_id {
+        Some(task_id) => {
+            if tree.get_task(task_id).is_none() {
+                return Err(format!(
+                    "Task {} does not exist in the active project.",
+                    bold_tid(*task_id)
+                ));
+            }
+            vec![*task_id]
+        },
+        None => tree.get_root_ids(),
+    };
+
+    let mut renderer = TreeRenderer {
+        tree,
+        max_depth,
+        filter,
+        expanded: HashSet::new(),
+        shown_cache: HashMap::new(),
+        lines: Vec::new(),
+    };
+    for root_id in root_ids {
+        if renderer.is_shown(&root_id, 0) {
+            renderer.render_task(&root_id, "", None, 0);
+        }
+    }
+    Ok(renderer.lines.join("\n"))
+}
+
+
+/// Which tasks should be drawn when rendering a tree.
+pub enum StatusFilter {
+    All,
+    Available,
+    Status(TaskStatus),
+}
+
+impl StatusFilter {
+
+    /// Parse a status, "available" or "all" (the default).
+    pub fn from_opt_status_flag(
+        tree: &TaskTree,
+        opt_status_flag: Option<String>,
+    ) -> Result<Self, String> {
+        match opt_status_flag {
+            None => Ok(Self::All),
+            Some(status_flag) => match &status_flag[..] {
+                "all" => Ok(Self::All),
+                "available" => Ok(Self::Available),
+                _ => Ok(Self::Status(tree.parse_status(&status_flag)?)),
+            },
+        }
+    }
+
+    pub fn matches(&self, tree: &TaskTree, task_id: &TID) -> bool {
+        match self {
+            Self::All => true,
+            Self::Available => tree.is_available(task_id),
+            Self::Status(status) => tree.get_task(task_id).unwrap().get_status() == status,
+        }
+    }
+
+}
+
+
+struct TreeRenderer<'a> {
+    tree: &'a TaskTree,
+    max_depth: Option<usize>,
+    filter: StatusFilter,
+    expanded: HashSet<TID>,
+    shown_cache: HashMap<(TID, usize), bool>,
+    lines: Vec<String>,
+}
+
+impl TreeRenderer<'_> {
+
+    /// Draw the task at the given depth, followed by its dependencies. `is_last` is None for a
+    /// root task, and otherwise says whether the task is the last of its siblings.
+    fn render_task(&mut self, task_id: &TID, prefix: &str, is_last: Option<bool>, depth: usize) {
+        let task = self.tree.get_task(task_id).unwrap();
+        let (branch, child_prefix) = match is_last {
+            None => ("", String::new()),
+            Some(true) => (LAST_BRANCH, format!("{}{}", prefix, BLANK)),
+            Some(false) => (BRANCH, format!("{}{}", prefix, PIPE)),
+        };
+        let mut line = format!(
+            "{}{}{} {}: {}",
+            prefix,
+            branch,
+            status_marker(task.get_status()),
+            task_id,
+            task.get_name(),
+        );
+
+        if self.expanded.contains(task_id) {
+            line.push_str(SHARED_MARKER);
+            self.lines.push(line);
+            return;
+        }
+        self.lines.push(line);
+
+        // Only tasks whose dependencies are drawn count as expanded, so that a task first reached
+        // at the depth limit is still expanded where it's reached at a shallower depth.
+        if self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            return;
+        }
+        self.expanded.insert(*task_id);
+        let shown_children: Vec<TID> = self.sorted_children(task_id)
+            .into_iter()
+            .filter(|child_id| self.is_shown(child_id, depth + 1))
+            .collect();
+        for (idx, child_id) in shown_children.iter().enumerate() {
+            let is_last = idx == shown_children.len() - 1;
+            self.render_task(child_id, &child_prefix, Some(is_last), depth + 1);
+        }
+    }
+
+    /// Check whether a task at the given depth should be drawn, i.e. it matches the status filter
+    /// or one of its drawn dependencies does.
+    fn is_shown(&mut self, task_id: &TID, depth: usize) -> bool {
+        if let Some(shown) = self.shown_cache.get(&(*task_id, depth)) {
+            return *shown;
+        }
+        let mut shown = self.filter.matches(self.tree, task_id);
+        if !shown && self.max_depth.is_none_or(|max_depth| depth < max_depth) {
+            for child_id in self.sorted_children(task_id) {
+                if self.is_shown(&child_id, depth + 1) {
+                    shown = true;
+                    break;
+                }
+            }
+        }
+        self.shown_cache.insert((*task_id, depth), shown);
+        shown
+    }
+
+    fn sorted_children(&self, task_id: &TID) -> Vec<TID> {
+        let mut children = self.tree.get_children(task_id).unwrap().clone();
+        children.sort();
+        children
+    }
+
+}
+
+
+/// Supported graph export formats.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum ExportFormat {
+    Dot,
+    Mermaid,
+}
+
+impl ExportFormat {
+
+    pub fn from_format_flag(format_flag: &str) -> Result<Self, String> {
+        match format_flag {
+            "dot" => Ok(Self::Dot),
+            "mermaid" => Ok(Self::Mermaid),
+            _ => Err(format!("No such export format {}", bold_text(format_flag))),
+        }
+    }
+
+}
+
+
+/// Export the dependency graph as a Graphviz DOT or Mermaid diagram. Nodes are labeled with the
+/// task's ID and name and colored by status, and edges point from a task to its dependencies. If a
+/// task is given, only that task and its (transitive) dependencies are exported.
+pub fn export_graph(
+    tree: &TaskTree,
+    task_id: Option<&TID>,
+    format: ExportFormat,
+) -> Result<String, String> {
+    let mut task_ids: Vec<TID> = match task_id {
+        Some(task_id) => {
+            let mut task_ids: Vec<TID> = tree.get_dependencies(task_id, Some("all".to_string()))?
+                .into_iter()
+                .copied()
+                .collect();
+            task_ids.push(*task_id);
+            task_ids
+        },
+        None => tree.get_task_ids(),
+    };
+    task_ids.sort();
+
+    let mut edges = Vec::new();
+    for task_id in &task_ids {
+        let mut children = tree.get_children(task_id).unwrap().clone();
+        children.sort();
+        for child_id in children {
+            edges.push((*task_id, child_id));
+        }
+    }
+
+    Ok(match format {
+        ExportFormat::Dot => export_dot(tree, &task_ids, &edges),
+        ExportFormat::Mermaid => export_mermaid(tree, &task_ids, &edges),
+    })
+}
+
+fn export_dot(tree: &TaskTree, task_ids: &[TID], edges: &[(TID, TID)]) -> String {
+    let mut lines = vec![
+        "digraph tasktree {".to_string(),
+        "    node [shape=box, style=\"rounded,filled\"];".to_string(),
+    ];
+    for task_id in task_ids {
+        let task = tree.get_task(task_id).unwrap();
+        let label = format!("{}: {}", task_id, task.get_name())
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"");
+        lines.push(format!(
+            "    {} [label=\"{}\", fillcolor=\"{}\"];",
+            task_id,
+            label,
+            status_color(task.get_status()),
+        ));
+    }
+    for (task_id, child_id) in edges {
+        lines.push(format!("    {} -> {};", task_id, child_id));
+    }
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+fn export_mermaid(tree: &TaskTree, task_ids: &[TID], edges: &[(TID, TID)]) -> String {
+    let mut lines = vec!["flowchart TD".to_string()];
+    for task_id in task_ids {
+        let task = tree.get_task(task_id).unwrap();
+        let label = format!("{}: {}", task_id, task.get_name()).replace('"', "#quot;");
+        lines.push(format!(
+            "    t{}[\"{}\"]:::{}",
+            task_id,
+            label,
+            status_class(task.get_status()),
+        ));
+    }
+    for (task_id, child_id) in edges {
+        lines.push(format!("    t{} --> t{}", task_id, child_id));
+    }
+    let statuses = [
+        TaskStatus::Open,
+        TaskStatus::InProgress,
+        TaskStatus::Closed,
+        TaskStatus::Cancelled,
+        TaskStatus::Custom(String::new()),
+    ];
+    for status in statuses {
+        lines.push(format!(
+            "    classDef {} fill:{}",
+            status_class(&status),
+            status_color(&status),
+        ));
+    }
+    lines.join("\n")
+}
+
+fn status_color(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Open => "#ffffff",
+        TaskStatus::InProgress => "#fff3b0",
+        TaskStatus::Closed => "#c8e6c9",
+        TaskStatus::Cancelled => "#eeeeee",
+        TaskStatus::Custom(_) => "#e0e0e0",
+    }
+}
+
+fn status_class(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Open => "open",
+        TaskStatus::InProgress => "inProgress",
+        TaskStatus::Closed => "closed",
+        TaskStatus::Cancelled => "cancelled",
+        TaskStatus::Custom(_) => "custom",
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::tests::setup_tree;
+
+    #[test]
+    fn test_render_tree_marks_shared_dependencies() {
+        let tree = setup_tree();
+        let expected = [
+            "[O] 1: Task 1",
+            "├── [O] 2: Task 2",
+            "│   ├── [O] 3: Task 3",
+            "│   │   └── [O] 5: Task 5",
+            "│   └── [O] 4: Task 4",
+            "│       ├── [O] 5: Task 5 (*)",
+            "│       └── [O] 6: Task 6",
+            "└── [O] 7: Task 7",
+            "    └── [O] 6: Task 6 (*)",
+        ].join("\n");
+        assert_eq!(render_tree(&tree, None, None, None).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_render_tree_depth_and_root() {
+        let tree = setup_tree();
+        let expected = [
+            "[O] 2: Task 2",
+            "├── [O] 3: Task 3",
+            "└── [O] 4: Task 4",
+        ].join("\n");
+        assert_eq!(render_tree(&tree, Some(&2), Some(1), None).unwrap(), expected);
+        assert!(render_tree(&tree, Some(&42), None, None).is_err());
+    }
+
+    #[test]
+    fn test_render_tree_expands_past_depth_limit() {
+        let mut tree = TaskTree::new();
+        for name in ["Task 1", "Task 2", "Task 3", "Task 4"] {
+            tree.add_task(name.to_string(), None);
+        }
+        tree.add_dependency(&1, &2).unwrap();
+        tree.add_dependency(&2, &3).unwrap();
+        tree.add_dependency(&1, &3).unwrap();
+        tree.add_dependency(&3, &4).unwrap();
+
+        // Task 3 is first reached at the depth limit, so it's expanded where it's reached again.
+        let expected = [
+            "[O] 1: Task 1",
+            "├── [O] 2: Task 2",
+            "│   └── [O] 3: Task 3",
+            "└── [O] 3: Task 3",
+            "    └── [O] 4: Task 4",
+        ].join("\n");
+        assert_eq!(render_tree(&tree, None, Some(2), None).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_render_tree_status_filter() {
+        let mut tree = setup_tree();
+        tree.set_status(&6, "closed".to_string()).unwrap();
+        let expected = [
+            "[O] 1: Task 1",
+            "├── [O] 2: Task 2",
+            "│   └── [O] 4: Task 4",
+            "│       └── [C] 6: Task 6",
+            "└── [O] 7: Task 7",
+            "    └── [C] 6: Task 6 (*)",
+        ].join("\n");
+        let rendered = render_tree(&tree, None, None, Some("closed".to_string())).unwrap();
+        assert_eq!(rendered, expected);
+        assert!(render_tree(&tree, None, None, Some("bogus".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_export_dot_subgraph() {
+        let mut tree = setup_tree();
+        tree.set_status(&6, "closed".to_string()).unwrap();
+        let expected = [
+            "digraph tasktree {",
+            "    node [shape=box, style=\"rounded,filled\"];",
+            "    4 [label=\"4: Task 4\", fillcolor=\"#ffffff\"];",
+            "    5 [label=\"5: Task 5\", fillcolor=\"#ffffff\"];",
+            "    6 [label=\"6: Task 6\", fillcolor=\"#c8e6c9\"];",
+            "    4 -> 5;",
+            "    4 -> 6;",
+            "}",
+        ].join("\n");
+        assert_eq!(export_graph(&tree, Some(&4), ExportFormat::Dot).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_export_mermaid() {
+        let mut tree = TaskTree::new();
+        let tid1 = tree.add_task("Say \"hi\"".to_string(), None);
+        let tid2 = tree.add_task("Wave".to_string(), None);
+        tree.add_dependency(&tid1, &tid2).unwrap();
+        let exported = export_graph(&tree, None, ExportFormat::Mermaid).unwrap();
+        assert!(exported.starts_with("flowchart TD\n"));
+        assert!(exported.contains("    t1[\"1: Say #quot;hi#quot;\"]:::open\n"));
+        assert!(exported.contains("    t1 --> t2\n"));
+        assert!(exported.contains("    classDef closed fill:#c8e6c9"));
+        assert!(ExportFormat::from_format_flag("svg").is_err());
+    }
+}
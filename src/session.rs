@@ -0,0 +1,96 @@
+use crate::project::Project;
+
+
+/// Keeps the active project loaded between commands, so that commands run from the shell don't
+/// have to load it from disk again. Projects are saved to disk whenever they change, unless the
+/// session is in a transaction.
+#[derive(Default)]
+pub struct Session {
+    active: Option<Project>,
+    in_transaction: bool,
+    /// Whether the loaded project changed during the transaction.
+    changed: bool,
+}
+
+impl Session {
+
+    pub fn new() -> Self {
+        Session { active: None, in_transaction: false, changed: false }
+    }
+
+    /// Get a copy of the active project, or None if there is no active project. The project is
+    /// loaded from disk the first time, and again after another project becomes active.
+    pub fn load_active_project(&mut self) -> Result<Option<Project>, String> {
+        let active_name = match Project::get_active() {
+            Some(active_name) if Project::exists(&active_name)? => active_name,
+            _ => return Ok(None),
+        };
+        match &self.active {
+            Some(proj) if proj.get_name() == active_name => Ok(Some(proj.clone())),
+            _ => {
+                let proj = Project::load(&active_name)?;
+                self.active = Some(proj.clone());
+                Ok(Some(proj))
+            },
+        }
+    }
+
+    /// Save a project. If it's the loaded project, it replaces it. Otherwise, the loaded project
+    /// is forgotten, since the statuses of the tasks it depends on in the saved project may have
+    /// changed. In a transaction, only the loaded project can be saved, and it's only kept in
+    /// memory until the transaction is committed.
+    pub fn save(&mut self, proj: &mut Project) -> Result<(), String> {
+        let is_loaded = self.active.as_ref()
+            .is_some_and(|active| active.get_name() == proj.get_name());
+        if self.in_transaction {
+            if !is_loaded {
+                return Err(format!(
+                    "Only the active project can be changed here, not project {}.",
+                    proj.get_name(),
+                ));
+            }
+            self.active = Some(proj.clone());
+            self.changed = true;
+            return Ok(());
+        }
+        proj.save()?;
+        self.active = if is_loaded { Some(proj.clone()) } else { None };
+        Ok(())
+    }
+
+    /// Forget the loaded project, e.g. after it was replaced or removed, so that it's loaded from
+    /// disk again when it's needed.
+    pub fn forget(&mut self) {
+        self.active = None;
+    }
+
+    /// Start keeping the changes to the loaded project in memory, until they're committed or
+    /// rolled back.
+    pub fn begin_transaction(&mut self) {
+        self.in_transaction = true;
+        self.changed = false;
+    }
+
+    /// Save the loaded project, if it changed during the transaction, and end the transaction.
+    pub fn commit(&mut self) -> Result<(), String> {
+        self.in_transaction = false;
+        if !self.changed {
+            return Ok(());
+        }
+        self.changed = false;
+        match &mut self.active {
+            Some(proj) => proj.save(),
+            None => Ok(()),
+        }
+    }
+
+    /// Drop the changes made during the transaction, and end it.
+    pub fn rollback(&mut self) {
+        self.in_transaction = false;
+        if self.changed {
+            self.changed = false;
+            self.active = None;
+        }
+    }
+
+}
@@ -0,0 +1,177 @@
+use rustyline::completion::{ Completer, Pair };
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{ CompletionType, Config, Context, Editor, Helper };
+use crate::args;
+use crate::command::Command;
+use crate::project::Project;
+use crate::session::Session;
+use crate::tree::TID;
+
+
+const HISTORY_FILE: &str = "shell_history";
+const MAX_HISTORY_SIZE: usize = 1000;
+const WELCOME_MSG: &str = "tasktree shell. Run \"help\" to list the actions, and \"exit\" (or \
+                           press Ctrl-D) to leave.";
+
+
+/// Run the interactive shell until the user leaves it. Each line is a command line as given to
+/// tasktree (without "tasktree"), and may quote arguments with spaces. The active project stays
+/// loaded in the session between commands.
+pub fn run(session: &mut Session) -> Result<String, String> {
+    let config = Config::builder()
+        .max_history_size(MAX_HISTORY_SIZE)
+        .and_then(|builder| builder.history_ignore_dups(true))
+        .map_err(|err| format!("Could not start the shell: {}", err))?
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::with_config(config)
+        .map_err(|err| format!("Could not start the shell: {}", err))?;
+    editor.set_helper(Some(ShellHelper));
+    let history_path = Project::get_data_path(HISTORY_FILE);
+    // There's no history file until the shell is first left.
+    let _ = editor.load_history(&history_path);
+
+    println!("{}", WELCOME_MSG);
+    loop {
+        let line = match editor.readline(&get_prompt()) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(format!("Could not read the command: {}", err)),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if line == "exit" || line == "quit" {
+            break;
+        }
+        run_line(line, session);
+    }
+
+    if editor.save_history(&history_path).is_err() {
+        return Err("Could not save the shell history.".to_string());
+    }
+    Ok(String::new())
+}
+
+/// Run one command line, printing its output like the tasktree binary does.
+fn run_line(line: &str, session: &mut Session) {
+    let words = match args::split_words(line) {
+        Ok(words) => words,
+        Err(msg) => return println!("{}", msg),
+    };
+    let words = match Command::expand_alias(words) {
+        Ok(words) => words,
+        Err(msg) => return println!("{}", msg),
+    };
+    if words[0] == "shell" {
+        return println!("Already in the shell.");
+    }
+    match Command::from_args(words) {
+        Err(msg) => println!("{}", msg),
+        Ok(command) => match command.execute(session) {
+            Err(msg) => println!("Error: {}", msg),
+            Ok(response) if response.is_empty() => (),
+            Ok(response) => println!("{}", response),
+        },
+    }
+}
+
+fn get_prompt() -> String {
+    match Project::get_active() {
+        Some(active_name) => format!("tasktree ({})> ", active_name),
+        None => "tasktree> ".to_string(),
+    }
+}
+
+/// Get the words to complete for the part of a line before the cursor, and where the last word
+/// starts. The last word is empty if the line is empty or ends with whitespace. Returns None in
+/// the middle of a quoted word.
+fn get_completion_words(line: &str) -> Option<(usize, Vec<String>)> {
+    let mut words = args::split_words(line).ok()?;
+    let start = match line.rfind(char::is_whitespace) {
+        Some(idx) => idx + 1,
+        None => 0,
+    };
+    if start == line.len() {
+        words.push(String::new());
+    }
+    Some((start, words))
+}
+
+/// Task IDs are shown with their tasks' names, since IDs alone are hard to tell apart.
+fn is_task_id(value: &str) -> bool {
+    value.rsplit(':').next().unwrap().parse::<TID>().is_ok()
+}
+
+
+/// Completes command lines in the shell like the completion scripts do.
+struct ShellHelper;
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, words) = match get_completion_words(&line[..pos]) {
+            Some(completion_words) => completion_words,
+            None => return Ok((pos, Vec::new())),
+        };
+        let pairs = Command::complete(&words).into_iter()
+            .map(|candidate| Pair {
+                display: if is_task_id(&candidate.value) && !candidate.description.is_empty() {
+                    format!("{} ({})", candidate.value, candidate.description)
+                } else {
+                    candidate.value.clone()
+                },
+                replacement: candidate.value,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_get_completion_words() {
+        assert_eq!(get_completion_words(""), Some((0, to_args(&[""]))));
+        assert_eq!(get_completion_words("se"), Some((0, to_args(&["se"]))));
+        assert_eq!(get_completion_words("set 3 "), Some((6, to_args(&["set", "3", ""]))));
+        assert_eq!(
+            get_completion_words("add-dep 3 oth"),
+            Some((10, to_args(&["add-dep", "3", "oth"]))),
+        );
+        assert_eq!(get_completion_words("new \"Write d"), None);
+        assert!(is_task_id("12"));
+        assert!(is_task_id("other:12"));
+        assert!(!is_task_id("closed"));
+    }
+}
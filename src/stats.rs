@@ -0,0 +1,429 @@
+use chrono::{ DateTime, Datelike, Duration, Local, NaiveDate, TimeZone, Utc };
+use serde::Serialize;
+use crate::tree::{ StatusChange, Task, TaskStatus, TaskTree, TID };
+use crate::command::{ bold_text, bold_tid, status_marker, underline_text };
+
+
+const PROGRESS_BAR_WIDTH: usize = 20;
+const BURNDOWN_CHART_WIDTH: usize = 40;
+
+
+/// Metrics about how tasks in a project move through their statuses. Only tasks whose status
+/// changes have been recorded contribute to them.
+#[derive(PartialEq, Debug)]
+pub struct ProjectStats {
+    /// Time from creation to completion, for each completed task.
+    pub lead_times: Vec<Duration>,
+    /// Time from first starting work (in-progress) to completion, for each completed task which
+    /// was ever in progress.
+    pub cycle_times: Vec<Duration>,
+    /// The number of tasks completed in each of the last weeks, oldest first. Weeks start on
+    /// Monday.
+    pub throughput: Vec<(NaiveDate, usize)>,
+    /// Tasks which are currently in progress, with how long they have been, oldest first.
+    pub aging: Vec<(TID, Duration)>,
+}
+
+
+/// Compute stats for the tasks in a tree as of `now`, with throughput for the last `weeks` weeks
+/// (including the current one). A task counts as completed when it has a done status other than
+/// cancelled, as of when it last got that status.
+pub fn compute_stats(tree: &TaskTree, now: DateTime<Utc>, weeks: usize) -> ProjectStats {
+    let today = now.naive_utc().date();
+    let this_week = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let mut throughput: Vec<(NaiveDate, usize)> = (0..weeks)
+        .rev()
+        .map(|weeks_ago| (this_week - Duration::weeks(weeks_ago as i64), 0))
+        .collect();
+
+    let mut stats = ProjectStats {
+        lead_times: Vec::new(),
+        cycle_times: Vec::new(),
+        throughput: Vec::new(),
+        aging: Vec::new(),
+    };
+    for task_id in tree.get_task_ids() {
+        let task = tree.get_task(&task_id).unwrap();
+        let history = task.get_status_history();
+        let last_change = match history.last() {
+            Some(last_change) => last_change,
+            None => continue,
+        };
+
+        if *task.get_status() == TaskStatus::InProgress {
+            stats.aging.push((task_id, now - last_change.timestamp));
+            continue;
+        }
+        if !is_completed(tree, task) {
+            continue;
+        }
+        let completed_at = last_change.timestamp;
+        stats.lead_times.push(completed_at - *task.get_created_timestamp());
+        if let Some(started) = first_with_status(history, &TaskStatus::InProgress) {
+            stats.cycle_times.push(completed_at - started.timestamp);
+        }
+        let completed_date = completed_at.naive_utc().date();
+        if let Some(week) = throughput.iter_mut()
+            .rev()
+            .find(|(week_start, _)| *week_start <= completed_date)
+        {
+            week.1 += 1;
+        }
+    }
+
+    stats.aging.sort_by(|(id1, age1), (id2, age2)| age2.cmp(age1).then(id1.cmp(id2)));
+    stats.throughput = throughput;
+    stats
+}
+
+/// Describe a project's stats.
+pub fn format_stats(tree: &TaskTree, stats: &ProjectStats) -> String {
+    let mut lines = vec![
+        format!("{} {}", bold_text("lead time:"), summarize_durations(&stats.lead_times)),
+        format!("{} {}", bold_text("cycle time:"), summarize_durations(&stats.cycle_times)),
+        underline_text("throughput per week:"),
+    ];
+    for (week_start, completed) in &stats.throughput {
+        lines.push(format!("{}: {}", week_start.format("%Y-%m-%d"), completed));
+    }
+
+    lines.push(underline_text("aging in progress:"));
+    if stats.aging.is_empty() {
+        lines.push("none".to_string());
+    }
+    for (task_id, age) in &stats.aging {
+        lines.push(format!(
+            "{}: {} ({})",
+            bold_tid(*task_id),
+            tree.get_task(task_id).unwrap().get_name(),
+            format_duration(age),
+        ));
+    }
+    lines.join("\n")
+}
+
+/// The length of the periods in a burndown.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Bucket {
+    Day,
+    Week,
+}
+
+impl Bucket {
+
+    pub fn from_bucket_flag(bucket_flag: &str) -> Result<Self, String> {
+        match bucket_flag {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            _ => Err(format!("No such bucket {}", bold_text(bucket_flag))),
+        }
+    }
+
+    /// Get the first day of the period containing the given date. Weeks start on Monday.
+    fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        }
+    }
+
+    fn length(&self) -> Duration {
+        match self {
+            Self::Day => Duration::days(1),
+            Self::Week => Duration::weeks(1),
+        }
+    }
+
+}
+
+
+/// Supported burndown output formats.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum BurndownFormat {
+    Chart,
+    Csv,
+    Json,
+}
+
+impl BurndownFormat {
+
+    pub fn from_format_flag(format_flag: &str) -> Result<Self, String> {
+        match format_flag {
+            "chart" => Ok(Self::Chart),
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("No such burndown format {}", bold_text(format_flag))),
+        }
+    }
+
+}
+
+
+/// The number of remaining (open) and completed (closed) tasks at the end of a period. Cancelled
+/// tasks are in neither.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct BurndownPoint {
+    /// The first day of the period.
+    pub date: NaiveDate,
+    pub open: usize,
+    pub closed: usize,
+}
+
+
+/// Compute how many of the given tasks were open and closed at the end of each period from the
+/// one containing `since` up to the one containing `now`, based on the tasks' status histories.
+/// Periods are in the local timezone; for the current period, counts are as of `now`.
+pub fn compute_burndown(
+    tree: &TaskTree,
+    task_ids: &[TID],
+    since: NaiveDate,
+    bucket: Bucket,
+    now: DateTime<Utc>,
+) -> Vec<BurndownPoint> {
+    let mut points = Vec::new();
+    let mut period_start = bucket.start_of(since);
+    let today = now.with_timezone(&Local).naive_local().date();
+    while period_start <= today {
+        let period_end = period_start + bucket.length();
+        let midnight = period_end.and_hms_opt(0, 0, 0).unwrap();
+        let end_time = match Local.from_local_datetime(&midnight).earliest() {
+            Some(end_time) => end_time.with_timezone(&Utc).min(now),
+            None => now,
+        };
+        let mut point = BurndownPoint { date: period_start, open: 0, closed: 0 };
+        for task_id in task_ids {
+            let task = tree.get_task(task_id);
+            let status = match task.and_then(|task| task.get_status_at(&end_time)) {
+                Some(status) => status,
+                None => continue,
+            };
+            match status {
+                TaskStatus::Cancelled => (),
+                status if tree.get_workflow().is_done(status) => point.closed += 1,
+                _ => point.open += 1,
+            }
+        }
+        points.push(point);
+        period_start = period_end;
+    }
+    points
+}
+
+/// Format a burndown in the given format. The chart has a bar for each period, where "#" stands
+/// for closed tasks and "." for open ones.
+pub fn format_burndown(points: &[BurndownPoint], format: BurndownFormat) -> String {
+    match format {
+        BurndownFormat::Csv => {
+            let mut lines = vec!["date,open,closed".to_string()];
+            for point in points {
+                lines.push(format!("{},{},{}", point.date, point.open, point.closed));
+            }
+            lines.join("\n")
+        },
+        BurndownFormat::Json => serde_json::to_string_pretty(points).unwrap(),
+        BurndownFormat::Chart => {
+            let max_total = points.iter().map(|point| point.open + point.closed).max().unwrap_or(0);
+            let scale = |count: usize| match max_total {
+                0 => 0,
+                _ => (count * BURNDOWN_CHART_WIDTH + max_total / 2) / max_total,
+            };
+            let mut lines = Vec::new();
+            for point in points {
+                let closed_width = scale(point.closed);
+                let open_width = scale(point.open + point.closed) - closed_width;
+                lines.push(format!(
+                    "{} {}{}{} {} open, {} closed",
+                    point.date,
+                    "#".repeat(closed_width),
+                    ".".repeat(open_width),
+                    " ".repeat(BURNDOWN_CHART_WIDTH - closed_width - open_width),
+                    point.open,
+                    point.closed,
+                ));
+            }
+            lines.join("\n")
+        },
+    }
+}
+
+/// Summarize the progress of the given tasks: a progress bar for how many of them are completed,
+/// followed by the number and percentage of tasks with each status in the tree's workflow.
+/// Cancelled tasks are listed, but don't count towards the percentages.
+pub fn format_progress(tree: &TaskTree, task_ids: &[TID]) -> String {
+    let counts = tree.count_completion(task_ids);
+    let mut lines = vec![format!(
+        "{} {}% ({}/{} completed)",
+        progress_bar(counts.completed, counts.total),
+        percentage(counts.completed, counts.total),
+        counts.completed,
+        counts.total,
+    )];
+    for state in tree.get_workflow().get_states() {
+        let status = TaskStatus::from_name(state.get_name());
+        let count = task_ids.iter()
+            .filter_map(|task_id| tree.get_task(task_id))
+            .filter(|task| *task.get_status() == status)
+            .count();
+        let share = match status {
+            TaskStatus::Cancelled => "not counted".to_string(),
+            _ => format!("{}%", percentage(count, counts.total)),
+        };
+        lines.push(format!(
+            "{} {}: {} ({})",
+            status_marker(&status),
+            bold_text(state.get_name()),
+            count,
+            share,
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Draw a text progress bar, e.g. "[#####---------------]".
+fn progress_bar(done: usize, total: usize) -> String {
+    let filled = match total {
+        0 => 0,
+        _ => done * PROGRESS_BAR_WIDTH / total,
+    };
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(PROGRESS_BAR_WIDTH - filled))
+}
+
+/// Get `part` as a percentage of `total`, rounded to the nearest whole number.
+fn percentage(part: usize, total: usize) -> usize {
+    match total {
+        0 => 0,
+        _ => (part * 200 + total) / (total * 2),
+    }
+}
+
+/// Format a duration as days and hours, or hours and minutes if it's shorter than a day.
+pub fn format_duration(duration: &Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    let (days, hours, minutes) = (minutes / (60 * 24), minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+/// Summarize durations by their average and median.
+fn summarize_durations(durations: &[Duration]) -> String {
+    if durations.is_empty() {
+        return "no completed tasks".to_string();
+    }
+    let mut sorted = durations.to_vec();
+    sorted.sort();
+    let total = sorted.iter().fold(Duration::zero(), |total, duration| total + *duration);
+    let average = total / sorted.len() as i32;
+    let median = sorted[sorted.len() / 2];
+    format!(
+        "average {}, median {} over {} tasks",
+        format_duration(&average),
+        format_duration(&median),
+        sorted.len(),
+    )
+}
+
+fn is_completed(tree: &TaskTree, task: &Task) -> bool {
+    *task.get_status() != TaskStatus::Cancelled && tree.is_done(task.get_id())
+}
+
+fn first_with_status<'a>(
+    history: &'a [StatusChange],
+    status: &TaskStatus,
+) -> Option<&'a StatusChange> {
+    history.iter().find(|change| change.status == *status)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::tests::{ set_status_at, setup_tree };
+
+    #[test]
+    fn test_compute_stats() {
+        let mut tree = setup_tree();
+        let created = |tree: &TaskTree, task_id: TID| {
+            *tree.get_task(&task_id).unwrap().get_created_timestamp()
+        };
+        let day = Duration::days(1);
+        let (created5, created6) = (created(&tree, 5), created(&tree, 6));
+        set_status_at(&mut tree, 5, "in-progress", created5 + day);
+        set_status_at(&mut tree, 5, "closed", created5 + day * 3);
+        set_status_at(&mut tree, 6, "closed", created6 + day * 2);
+        let now = created5 + day * 5;
+        set_status_at(&mut tree, 3, "in-progress", now - day);
+        set_status_at(&mut tree, 4, "cancelled", now - day);
+
+        let stats = compute_stats(&tree, now, 2);
+        assert_eq!(stats.lead_times, vec![day * 3, day * 2]);
+        assert_eq!(stats.cycle_times, vec![day * 2]);
+        assert_eq!(stats.aging, vec![(3, day)]);
+        let completed: usize = stats.throughput.iter().map(|(_, completed)| completed).sum();
+        assert_eq!(completed, 2);
+        assert_eq!(stats.throughput.last().unwrap().0.weekday(), chrono::Weekday::Mon);
+    }
+
+    #[test]
+    fn test_format_progress() {
+        let mut tree = setup_tree();
+        tree.set_status(&5, "closed".to_string()).unwrap();
+        tree.set_status(&3, "closed".to_string()).unwrap();
+        tree.set_status(&6, "cancelled".to_string()).unwrap();
+        tree.set_status(&4, "in-progress".to_string()).unwrap();
+        let dep_ids: Vec<TID> = tree.get_dependencies(&2, Some("all".to_string())).unwrap()
+            .into_iter()
+            .copied()
+            .collect();
+        let expected = [
+            "[#############-------] 67% (2/3 completed)".to_string(),
+            format!("[O] {}: 0 (0%)", bold_text("open")),
+            format!("[I] {}: 1 (33%)", bold_text("in-progress")),
+            format!("[C] {}: 2 (67%)", bold_text("closed")),
+            format!("[X] {}: 1 (not counted)", bold_text("cancelled")),
+        ].join("\n");
+        assert_eq!(format_progress(&tree, &dep_ids), expected);
+        assert_eq!(progress_bar(0, 0), format!("[{}]", "-".repeat(PROGRESS_BAR_WIDTH)));
+    }
+
+    #[test]
+    fn test_burndown() {
+        let mut tree = TaskTree::new();
+        let tid1 = tree.add_task("Goal".to_string(), None);
+        let tid2 = tree.add_task("Step".to_string(), None);
+        let tid3 = tree.add_task("Dropped".to_string(), None);
+        let now = *tree.get_task(&tid3).unwrap().get_created_timestamp() + Duration::days(3);
+        set_status_at(&mut tree, tid2, "closed", now - Duration::days(1));
+        set_status_at(&mut tree, tid3, "cancelled", now);
+
+        let today = now.with_timezone(&Local).naive_local().date();
+        let since = today - Duration::days(1);
+        let points = compute_burndown(&tree, &[tid1, tid2, tid3], since, Bucket::Day, now);
+        let expected = vec![
+            BurndownPoint { date: since, open: 2, closed: 1 },
+            BurndownPoint { date: today, open: 1, closed: 1 },
+        ];
+        assert_eq!(points, expected);
+
+        let csv = format_burndown(&points, BurndownFormat::Csv);
+        assert_eq!(csv, format!("date,open,closed\n{},2,1\n{},1,1", since, today));
+        let json: serde_json::Value =
+            serde_json::from_str(&format_burndown(&points, BurndownFormat::Json)).unwrap();
+        assert_eq!(json[1]["open"], 1);
+        let chart = format_burndown(&points, BurndownFormat::Chart);
+        let first_bar = format!("{} {}{} 2 open", since, "#".repeat(13), ".".repeat(27));
+        assert!(chart.starts_with(&first_bar));
+
+        let weeks = compute_burndown(&tree, &[tid1], since, Bucket::Week, now);
+        assert_eq!(weeks.last().unwrap().date.weekday(), chrono::Weekday::Mon);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(&Duration::minutes(42)), "42m");
+        assert_eq!(format_duration(&Duration::minutes(125)), "2h 5m");
+        assert_eq!(format_duration(&(Duration::days(3) + Duration::hours(4))), "3d 4h");
+    }
+}
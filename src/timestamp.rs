@@ -0,0 +1,75 @@
+use chrono::format::{ Item, StrftimeItems };
+use chrono::prelude::*;
+use serde::{ Deserialize, Deserializer };
+use std::env;
+use crate::config;
+
+
+/// The format timestamps used to be saved in. These timestamps were in UTC.
+pub const LEGACY_DATE_FORMAT: &str = "%m-%d-%Y %H:%M";
+/// The default format for displaying timestamps.
+pub const DEFAULT_DISPLAY_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// The environment variable which overrides the format for displaying timestamps.
+pub const DISPLAY_FORMAT_VAR: &str = "TASKTREE_DATE_FORMAT";
+
+
+/// Parse a saved timestamp, either in RFC 3339 or in the legacy format.
+pub fn parse(timestamp: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(timestamp) {
+        return Ok(parsed.with_timezone(&Utc));
+    }
+    match NaiveDateTime::parse_from_str(timestamp, LEGACY_DATE_FORMAT) {
+        Ok(naive) => Ok(Utc.from_utc_datetime(&naive)),
+        Err(_) => Err(format!("Invalid timestamp {}", timestamp)),
+    }
+}
+
+/// Deserialize a timestamp with `parse`, so projects saved with legacy timestamps still load.
+/// Timestamps are always serialized as RFC 3339.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let timestamp = String::deserialize(deserializer)?;
+    parse(&timestamp).map_err(serde::de::Error::custom)
+}
+
+/// Format a timestamp for display in the local timezone. The format can be set with the
+/// TASKTREE_DATE_FORMAT environment variable or the "date-format" setting (using strftime
+/// syntax); invalid formats fall back to the default.
+pub fn format_local(timestamp: &DateTime<Utc>) -> String {
+    let display_format = match env::var(DISPLAY_FORMAT_VAR) {
+        Ok(display_format) if is_valid_format(&display_format) => display_format,
+        _ => config::with_active(|settings| settings.date_format.clone())
+            .unwrap_or_else(|| DEFAULT_DISPLAY_FORMAT.to_string()),
+    };
+    timestamp.with_timezone(&Local).format(&display_format).to_string()
+}
+
+pub fn is_valid_format(display_format: &str) -> bool {
+    !display_format.is_empty()
+        && StrftimeItems::new(display_format).all(|item| !matches!(item, Item::Error))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamps() {
+        let naive = NaiveDate::from_ymd_opt(2021, 3, 14).unwrap().and_hms_opt(15, 9, 0).unwrap();
+        let expected = Utc.from_utc_datetime(&naive);
+        assert_eq!(parse("2021-03-14T15:09:00Z").unwrap(), expected);
+        assert_eq!(parse("2021-03-14T16:09:00+01:00").unwrap(), expected);
+        assert_eq!(parse("03-14-2021 15:09").unwrap(), expected);
+        assert!(parse("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_valid_format() {
+        assert!(is_valid_format("%d.%m.%Y %H:%M:%S"));
+        assert!(!is_valid_format("%Q"));
+        assert!(!is_valid_format(""));
+    }
+}
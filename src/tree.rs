@@ -1,21 +1,44 @@
 use std::collections::HashMap;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashSet;
+use std::fmt;
 use chrono::prelude::*;
-use crate::project::DATE_FORMAT;
-use crate::command::{ bold_text, bold_tid, underline_text };
+use crate::timestamp;
+use crate::command::{ bold_text, bold_tid, styled_repr, underline_text };
+use crate::workflow::Workflow;
 
 
 pub type TID = u16;
 
 
 /// A struct representing a project's task dependency graph (tasktree).
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct TaskTree {
     id_counter: TID,
+    #[serde(deserialize_with = "deserialize_tasks")]
     tasks: HashMap<TID, Box<Task>> ,
     children: HashMap<TID, Vec<TID>>,
     parents: HashMap<TID, Vec<TID>>,
+    #[serde(default)]
+    reopen_policy: ReopenPolicy,
+    #[serde(default)]
+    workflow: Workflow,
+    #[serde(default)]
+    external_children: HashMap<TID, Vec<ExternalRef>>,
+    /// Whether the tasks in other projects which this tree's tasks depend on are done, according
+    /// to their own project's workflow. These aren't saved; the project fills them in when it's
+    /// loaded.
+    #[serde(skip)]
+    external_done: HashMap<ExternalRef, bool>,
+    /// The task which was created or changed last, which "@last" refers to.
+    #[serde(default)]
+    last_touched: Option<TID>,
+}
+
+impl Default for TaskTree {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TaskTree {
@@ -26,6 +49,11 @@ impl TaskTree {
             tasks: HashMap::new(),
             children: HashMap::new(),
             parents: HashMap::new(),
+            reopen_policy: ReopenPolicy::default(),
+            workflow: Workflow::default(),
+            external_children: HashMap::new(),
+            external_done: HashMap::new(),
+            last_touched: None,
         }
     }
 
@@ -36,15 +64,65 @@ impl TaskTree {
         self.children.insert(id, Vec::new());
         self.parents.insert(id, Vec::new());
         self.id_counter += 1;
+        self.last_touched = Some(id);
         id
     }
 
-    /// Gets the representation of the task if exists.
-    pub fn get_task_repr(&self, task_id: &TID) -> Option<String> {
-        match self.tasks.get(task_id) {
-            None => None,
-            Some(task) => Some(task.get_repr().to_string()),
+    /// Resolve a reference to a task: its TID, "@last" for the task which was created or changed
+    /// last, or (ignoring case) its name or slug (e.g. "build-api" for "Build API"). Otherwise, a
+    /// unique prefix of the name or slug, or else a unique fuzzy match (the reference's characters
+    /// appear in the name in order) is used. If several tasks match, they're listed in the error.
+    pub fn resolve_task(&self, reference: &str) -> Result<TID, String> {
+        if let Ok(task_id) = reference.parse() {
+            return Ok(task_id);
+        }
+        if reference == "@last" {
+            return self.last_touched
+                .filter(|task_id| self.tasks.contains_key(task_id))
+                .ok_or_else(|| "No task was created or changed yet.".to_string());
         }
+
+        let query = reference.trim().to_lowercase();
+        if query.is_empty() {
+            return Err("The task to use is missing.".to_string());
+        }
+        let fuzzy_query: String = query.split_whitespace().collect();
+        // Exact matches, then prefixes, then fuzzy matches.
+        for pass in 0..3 {
+            let mut task_ids: Vec<TID> = self.tasks.values()
+                .filter(|task| {
+                    let name = task.get_name().to_lowercase();
+                    let slug = get_slug(&name);
+                    match pass {
+                        0 => name == query || slug == query,
+                        1 => name.starts_with(&query) || slug.starts_with(&query),
+                        _ => is_subsequence(&fuzzy_query, &name),
+                    }
+                })
+                .map(|task| task.id)
+                .collect();
+            task_ids.sort();
+            match task_ids[..] {
+                [] => continue,
+                [task_id] => return Ok(task_id),
+                _ => {
+                    let reprs: Vec<String> = task_ids.iter()
+                        .map(|task_id| styled_repr(self.tasks.get(task_id).unwrap()))
+                        .collect();
+                    return Err(format!(
+                        "\"{}\" matches several tasks; use one of their ids:\n{}",
+                        reference,
+                        reprs.join("\n"),
+                    ));
+                },
+            }
+        }
+        Err(format!("No task matches \"{}\".", reference))
+    }
+
+    /// Gets the representation of the task if exists, styled for output.
+    pub fn get_task_repr(&self, task_id: &TID) -> Option<String> {
+        self.tasks.get(task_id).map(|task| styled_repr(task))
     }
 
     /// Removes the task with the given TID from the tree. Also removes all of its dependencies and 
@@ -68,33 +146,273 @@ impl TaskTree {
             // TODO: add orphan check
 
             self.tasks.remove(task_id);
+            self.children.remove(task_id);
+            self.parents.remove(task_id);
+            self.external_children.remove(task_id);
+            if self.last_touched == Some(*task_id) {
+                self.last_touched = None;
+            }
             Ok(())
         } else {
             Err(format!("No task with id {} in active project.", bold_tid(*task_id)))
         }
     }
 
+    /// Get the dependencies which `remove_task_splice` would add when removing the given task, as
+    /// (task, dependency) pairs in ascending order. Nothing is modified.
+    pub fn preview_splice(&self, task_id: &TID) -> Result<Vec<(TID, TID)>, String> {
+        self.check_task_exists(task_id)?;
+        let mut new_edges = Vec::new();
+        for parent_id in self.parents.get(task_id).unwrap() {
+            for child_id in self.children.get(task_id).unwrap() {
+                if !self.children.get(parent_id).unwrap().contains(child_id) {
+                    new_edges.push((*parent_id, *child_id));
+                }
+            }
+        }
+        new_edges.sort();
+        Ok(new_edges)
+    }
+
+    /// Removes the task with the given TID from the tree, and makes each task which depended on it
+    /// depend on each of its dependencies instead. Dependencies which already exist are skipped.
+    /// Returns the added dependencies as (task, dependency) pairs.
+    pub fn remove_task_splice(&mut self, task_id: &TID) -> Result<Vec<(TID, TID)>, String> {
+        let new_edges = self.preview_splice(task_id)?;
+        self.remove_task(task_id)?;
+        for (parent_id, child_id) in &new_edges {
+            // The removed task was a path from parent to child, so this can't create a cycle.
+            self.add_dependency(parent_id, child_id)?;
+        }
+        Ok(new_edges)
+    }
+
+    /// Get the tasks which `remove_task_recursive` would remove, in ascending order. Nothing is
+    /// modified.
+    pub fn preview_remove_recursive(&self, task_id: &TID) -> Result<Vec<TID>, String> {
+        self.check_task_exists(task_id)?;
+        let mut removed = HashSet::new();
+        removed.insert(*task_id);
+        let mut to_visit = vec![*task_id];
+        while let Some(visit_id) = to_visit.pop() {
+            for child_id in self.children.get(&visit_id).unwrap() {
+                let needed_elsewhere = self.parents.get(child_id).unwrap()
+                    .iter()
+                    .any(|parent_id| !removed.contains(parent_id));
+                if !needed_elsewhere && removed.insert(*child_id) {
+                    to_visit.push(*child_id);
+                }
+            }
+        }
+
+        let mut removed: Vec<TID> = removed.into_iter().collect();
+        removed.sort();
+        Ok(removed)
+    }
+
+    /// Removes the task with the given TID along with every transitive dependency which no other
+    /// remaining task depends on. Returns the removed tasks.
+    pub fn remove_task_recursive(&mut self, task_id: &TID) -> Result<Vec<TID>, String> {
+        let removed = self.preview_remove_recursive(task_id)?;
+        for removed_id in &removed {
+            self.remove_task(removed_id)?;
+        }
+        Ok(removed)
+    }
+
+    /// Get the given task followed by all of its transitive dependencies (in ascending order).
+    pub fn get_subtree_ids(&self, task_id: &TID) -> Result<Vec<TID>, String> {
+        let mut dep_ids: Vec<TID> = self.get_dependencies(task_id, Some("all".to_string()))?
+            .into_iter()
+            .copied()
+            .collect();
+        dep_ids.sort();
+        dep_ids.insert(0, *task_id);
+        Ok(dep_ids)
+    }
+
+    /// Copy the given task (and, if `with_deps`, all of its transitive dependencies) into another
+    /// tree. The copies get new TIDs from the other tree, and dependencies between the copied tasks
+    /// are kept. Dependencies on tasks which aren't copied become dependencies on those tasks in
+    /// this tree's project, `source_project`. Returns a map from the copied tasks' TIDs to their
+    /// new TIDs.
+    pub fn copy_subgraph_to(
+        &self,
+        task_id: &TID,
+        with_deps: bool,
+        target: &mut TaskTree,
+        source_project: &str,
+    ) -> Result<HashMap<TID, TID>, String> {
+        let task_ids = match with_deps {
+            true => self.get_subtree_ids(task_id)?,
+            false => {
+                self.check_task_exists(task_id)?;
+                vec![*task_id]
+            },
+        };
+
+        let mut id_map = HashMap::new();
+        for old_id in &task_ids {
+            let new_id = target.id_counter;
+            let task = self.tasks.get(old_id).unwrap().with_id(new_id);
+            target.tasks.insert(new_id, Box::new(task));
+            target.children.insert(new_id, Vec::new());
+            target.parents.insert(new_id, Vec::new());
+            target.id_counter += 1;
+            id_map.insert(*old_id, new_id);
+        }
+        for old_id in &task_ids {
+            for child_id in self.children.get(old_id).unwrap() {
+                if let Some(new_child_id) = id_map.get(child_id) {
+                    target.add_dependency(&id_map[old_id], new_child_id)?;
+                    continue;
+                }
+                let left_behind = ExternalRef {
+                    project: source_project.to_string(),
+                    task_id: *child_id,
+                };
+                target.add_external_dependency(&id_map[old_id], left_behind.clone())?;
+                target.set_external_done(left_behind, self.is_done(child_id));
+            }
+            for external_ref in self.get_external_children(old_id) {
+                target.add_external_dependency(&id_map[old_id], external_ref.clone())?;
+                if let Some(done) = self.external_done.get(external_ref) {
+                    target.set_external_done(external_ref.clone(), *done);
+                }
+            }
+        }
+        Ok(id_map)
+    }
+
+    /// Move the given task (and, if `with_deps`, all of its transitive dependencies) into another
+    /// tree, as with `copy_subgraph_to`. Dependencies which tasks left in this tree still need are
+    /// copied rather than moved. Tasks left in this tree which depended on a moved task depend on
+    /// it in the target project instead. Returns a map from the carried tasks' TIDs to their new
+    /// TIDs.
+    pub fn move_subgraph_to(
+        &mut self,
+        task_id: &TID,
+        with_deps: bool,
+        target: &mut TaskTree,
+        source_project: &str,
+        target_project: &str,
+    ) -> Result<HashMap<TID, TID>, String> {
+        let id_map = self.copy_subgraph_to(task_id, with_deps, target, source_project)?;
+        let to_remove = match with_deps {
+            true => self.preview_remove_recursive(task_id)?,
+            false => vec![*task_id],
+        };
+        for remove_id in &to_remove {
+            let moved = ExternalRef {
+                project: target_project.to_string(),
+                task_id: id_map[remove_id],
+            };
+            let parents = self.parents.get(remove_id).unwrap().clone();
+            self.remove_task(remove_id)?;
+            for parent_id in parents.iter().filter(|parent_id| !to_remove.contains(parent_id)) {
+                self.add_external_dependency(parent_id, moved.clone())?;
+            }
+            self.set_external_done(moved, target.is_done(&id_map[remove_id]));
+        }
+        Ok(id_map)
+    }
+
+    /// Merge the task with drop_id into the task with keep_id. Every dependency and dependent of
+    /// the dropped task is moved onto the kept task, the dropped task's description is appended to
+    /// the kept task's, and the dropped task is removed. Nothing changes if the merge would create
+    /// a cycle.
+    pub fn merge_tasks(&mut self, keep_id: &TID, drop_id: &TID) -> Result<(), String> {
+        self.check_task_exists(keep_id)?;
+        self.check_task_exists(drop_id)?;
+        if keep_id == drop_id {
+            return Err(format!("Cannot merge task {} into itself.", bold_tid(*keep_id)));
+        }
+
+        let mut merged = self.clone();
+        let drop_children = merged.children.get(drop_id).unwrap().clone();
+        let drop_parents = merged.parents.get(drop_id).unwrap().clone();
+        let drop_external_children = merged.get_external_children(drop_id).to_vec();
+        let drop_desc = merged.tasks.get(drop_id).unwrap().desc.clone();
+        merged.remove_task(drop_id)?;
+
+        for child_id in drop_children.iter().filter(|id| *id != keep_id) {
+            if !merged.children.get(keep_id).unwrap().contains(child_id) {
+                merged.add_dependency(keep_id, child_id)?;
+            }
+        }
+        for parent_id in drop_parents.iter().filter(|id| *id != keep_id) {
+            if !merged.children.get(parent_id).unwrap().contains(keep_id) {
+                merged.add_dependency(parent_id, keep_id)?;
+            }
+        }
+        for external_ref in drop_external_children {
+            if !merged.get_external_children(keep_id).contains(&external_ref) {
+                merged.add_external_dependency(keep_id, external_ref)?;
+            }
+        }
+        let keep_task = merged.tasks.get_mut(keep_id).unwrap();
+        keep_task.desc = match (keep_task.desc.take(), drop_desc) {
+            (Some(keep_desc), Some(drop_desc)) => Some(format!("{}\n\n{}", keep_desc, drop_desc)),
+            (keep_desc, drop_desc) => keep_desc.or(drop_desc),
+        };
+
+        merged.last_touched = Some(*keep_id);
+        *self = merged;
+        Ok(())
+    }
+
+    /// Split the task with the given TID into new tasks with the given names. Each new task gets
+    /// the original's description and status, depends on each of its dependencies, and is depended
+    /// on by each of its dependents. The original task is removed. Returns the new tasks' TIDs.
+    pub fn split_task(&mut self, task_id: &TID, names: Vec<String>) -> Result<Vec<TID>, String> {
+        self.check_task_exists(task_id)?;
+        if names.is_empty() {
+            return Err(format!("No names given to split task {} into.", bold_tid(*task_id)));
+        }
+
+        let mut split = self.clone();
+        let original = split.tasks.get(task_id).unwrap().clone();
+        let children = split.children.get(task_id).unwrap().clone();
+        let parents = split.parents.get(task_id).unwrap().clone();
+        let external_children = split.get_external_children(task_id).to_vec();
+        split.remove_task(task_id)?;
+
+        let mut new_ids = Vec::new();
+        for name in names {
+            let new_id = split.add_task(name, original.desc.clone());
+            split.tasks.get_mut(&new_id).unwrap().set_status(original.status.clone());
+            for child_id in &children {
+                split.add_dependency(&new_id, child_id)?;
+            }
+            for parent_id in &parents {
+                split.add_dependency(parent_id, &new_id)?;
+            }
+            for external_ref in &external_children {
+                split.add_external_dependency(&new_id, external_ref.clone())?;
+            }
+            new_ids.push(new_id);
+        }
+
+        split.last_touched = new_ids.last().copied();
+        *self = split;
+        Ok(new_ids)
+    }
+
     /// View project tasks by the status. If no status flag is provided, shows all available tasks.
     /// If the status_flag is "all", view all tasks. If the provided status_flag is invalid,
     /// informs user.
     pub fn view_tasks(&self, status_flag: Option<String>) -> Result<Vec<&Task>, String> {
-        match status_flag {
-            None => return Ok(self.get_available_tasks()),
-            _ => ()
-        };
+        if status_flag.is_none() {
+            return Ok(self.get_available_tasks());
+        }
 
         let flag = status_flag.unwrap();
-        let parseable_status = match TaskStatus::from_status_flag(&flag) {
-            Ok(_) => true,
-            _ => false,
-        };
+        let parsed_status = self.workflow.parse_status(&flag);
 
-        if flag != "all" && !parseable_status {
+        if flag != "all" && parsed_status.is_err() {
             return Err(format!("Invalid status flag {}.", bold_text(&flag)));
         }
 
-        let parsed_status = TaskStatus::from_status_flag(&flag);
-
         let mut tasks = Vec::new();
         for task in self.tasks.values() {
             if flag == "all" || task.status == *parsed_status.as_ref().unwrap() {
@@ -106,14 +424,14 @@ impl TaskTree {
     }
 
     /// Search this tree's tasks with the given query and optional status flag. If no status
-    /// flag is provided, searches all tasks. Returns a vector of reprs for the matching tasks.
+    /// flag is provided, searches all tasks. Returns the tasks whose reprs match.
     pub fn search_tasks(
         &self,
         query: &str,
         opt_status_flag: Option<String>
-    ) -> Result<Vec<String>, String> {
-        let opt_status = TaskStatus::from_opt_status_flag(opt_status_flag)?;
-        let tasks_iter = self.tasks.values().into_iter();
+    ) -> Result<Vec<&Task>, String> {
+        let opt_status = self.workflow.parse_opt_status(opt_status_flag)?;
+        let tasks_iter = self.tasks.values();
         let tasks_to_search: Vec<&Box<Task>> = match opt_status {
             None => tasks_iter.collect(),
             Some(status) => tasks_iter.filter(|x| x.status == status).collect(),
@@ -121,9 +439,8 @@ impl TaskTree {
 
         let mut results = Vec::new();
         for task in tasks_to_search {
-            let task_repr = task.get_repr();
-            if task_repr.contains(query) {
-                results.push(task_repr.to_string());
+            if task.get_repr().contains(query) {
+                results.push(&**task);
             }
         }
         Ok(results)
@@ -132,7 +449,7 @@ impl TaskTree {
     /// Get a task's repr.
     pub fn view_task(&self, task_id: &TID) -> Result<String, String> {
         self.check_task_exists(task_id)?;
-        let task = &*self.tasks.get(task_id).unwrap();
+        let task = self.tasks.get(task_id).unwrap();
         let mut info = format!("{}\n", underline_text("Task Info"));
 
         info.push_str(&format!(
@@ -153,20 +470,29 @@ impl TaskTree {
         info.push_str(&format!(
             "{}: {}",
             bold_text("created"),
-            task.get_created_timestamp(),
+            timestamp::format_local(task.get_created_timestamp()),
         ));
+        let history = task.get_status_history();
+        let last_change = history.last()
+            .filter(|change| history.len() > 1 || change.status != TaskStatus::Open);
+        if let Some(change) = last_change {
+            info.push_str(&format!(
+                "\n{}: {}",
+                bold_text("status changed"),
+                timestamp::format_local(&change.timestamp),
+            ));
+        }
 
-        match task.get_desc() {
-            Some(desc) => info.push_str(&format!(
+        if let Some(desc) = task.get_desc() {
+            info.push_str(&format!(
                 "\n{}: {}",
                 bold_text("description"),
                 desc,
-            )),
-            _ => (),
+            ));
         }
 
         let children = self.children.get(task_id).unwrap();
-        if children.len() == 0 {
+        if children.is_empty() {
             return Ok(info);
         }
     
@@ -174,33 +500,336 @@ impl TaskTree {
         for child_id in children {
             info.push_str(&format!(
                 "{}\n",
-                self.get_task_repr(&child_id).unwrap()
+                self.get_task_repr(child_id).unwrap()
             ));
         }
         Ok(info)
     }
 
-    /// Set a task's status.
-    pub fn set_status(&mut self, task_id: &TID, status_flag: String) -> Result<(), String> {
+    /// Set a task's status. The status and the change to it must be allowed by the tree's
+    /// workflow. If this reopens a task which done tasks depend on, the tree's reopen policy
+    /// decides what happens to those dependents: the change is rejected, the dependents are left
+    /// done, or they are reopened as well. Returns the done dependents that were left done or
+    /// reopened, in ascending order.
+    pub fn set_status(&mut self, task_id: &TID, status_flag: String) -> Result<Vec<TID>, String> {
+        self.check_task_exists(task_id)?;
+        let status = self.workflow.parse_status(&status_flag)?;
+        self.workflow.check_transition(&self.tasks.get(task_id).unwrap().status, &status)?;
+        self.check_deps_done(task_id, &status)?;
+
+        let mut closed_dependents = Vec::new();
+        if !self.workflow.is_done(&status) {
+            let mut collected = HashSet::new();
+            self.collect_cascade(task_id, &self.parents, true, &mut collected);
+            closed_dependents = collected.into_iter().collect();
+            closed_dependents.sort();
+        }
+        if !closed_dependents.is_empty() {
+            match self.reopen_policy {
+                ReopenPolicy::Reject => return Err(format!(
+                    "Cannot set task {} as {}; closed tasks depend on it: {}",
+                    bold_tid(*task_id),
+                    bold_text(status.to_name()),
+                    closed_dependents.iter()
+                        .map(|id| bold_tid(*id))
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                )),
+                ReopenPolicy::Warn => (),
+                ReopenPolicy::Reopen => {
+                    for dependent_id in &closed_dependents {
+                        let old_status = &self.tasks.get(dependent_id).unwrap().status;
+                        let transition = self.workflow
+                            .check_transition(old_status, &TaskStatus::Open);
+                        if let Err(msg) = transition {
+                            return Err(format!("Task {}: {}", bold_tid(*dependent_id), msg));
+                        }
+                    }
+                    for dependent_id in &closed_dependents {
+                        (**self.tasks.get_mut(dependent_id).unwrap()).set_status(TaskStatus::Open);
+                    }
+                },
+            }
+        }
+        (**self.tasks.get_mut(task_id).unwrap()).set_status(status);
+        self.last_touched = Some(*task_id);
+        Ok(closed_dependents)
+    }
+
+    /// Get this tree's workflow.
+    pub fn get_workflow(&self) -> &Workflow {
+        &self.workflow
+    }
+
+    /// Get a mutable reference to this tree's workflow.
+    pub fn get_workflow_mut(&mut self) -> &mut Workflow {
+        &mut self.workflow
+    }
+
+    /// Parse a status flag, requiring the status to be part of this tree's workflow.
+    pub fn parse_status(&self, status_flag: &str) -> Result<TaskStatus, String> {
+        self.workflow.parse_status(status_flag)
+    }
+
+    /// Remove a status from this tree's workflow. Fails if any task has that status.
+    pub fn remove_workflow_state(&mut self, name: &str) -> Result<(), String> {
+        let status = self.workflow.parse_status(name)?;
+        let mut using: Vec<TID> = self.tasks.values()
+            .filter(|task| task.status == status)
+            .map(|task| task.id)
+            .collect();
+        if !using.is_empty() {
+            using.sort();
+            let using: Vec<String> = using.iter().map(|tid| tid.to_string()).collect();
+            return Err(format!(
+                "Cannot remove status {}; it is used by tasks {}",
+                bold_text(name),
+                using.join(", "),
+            ));
+        }
+        self.workflow.remove_state(name)
+    }
+
+    /// Get the policy for reopening tasks which closed tasks depend on.
+    pub fn get_reopen_policy(&self) -> ReopenPolicy {
+        self.reopen_policy
+    }
+
+    /// Set the policy for reopening tasks which closed tasks depend on.
+    pub fn set_reopen_policy(&mut self, reopen_policy: ReopenPolicy) {
+        self.reopen_policy = reopen_policy;
+    }
+
+    /// Find done (e.g. closed) tasks which depend on tasks that are not done. Tasks whose status
+    /// doesn't need their dependencies to be done (e.g. cancelled) are not inconsistent. Returns
+    /// (done task, dependency) pairs, in ascending order.
+    pub fn find_inconsistencies(&self) -> Vec<(TID, TID)> {
+        let mut inconsistencies = Vec::new();
+        for task_id in self.get_task_ids() {
+            if !self.is_completed_on_deps(&task_id) {
+                continue;
+            }
+            let mut children = self.children.get(&task_id).unwrap().clone();
+            children.sort();
+            for child_id in children {
+                if !self.is_done(&child_id) {
+                    inconsistencies.push((task_id, child_id));
+                }
+            }
+        }
+        inconsistencies
+    }
+
+    /// Get the tasks whose status would change if the given task's status were set with
+    /// `set_status_cascade`, in ascending order. Nothing is modified.
+    pub fn preview_status_cascade(
+        &self,
+        task_id: &TID,
+        status_flag: &str,
+    ) -> Result<Vec<TID>, String> {
+        Ok(self.plan_status_cascade(task_id, status_flag)?
+            .into_iter()
+            .map(|(affected_id, _)| affected_id)
+            .collect())
+    }
+
+    /// Set a task's status, and cascade the change through the graph. Setting a task to a done
+    /// status which needs its dependencies to be done (e.g. closed) also closes all of its
+    /// transitive dependencies which are not done yet. Setting a task to a status which isn't done
+    /// reopens every transitive dependent which was done on top of it. Other statuses (e.g.
+    /// cancelled) don't cascade. All of the status changes must be allowed by the tree's workflow.
+    /// Returns the tasks whose status changed, in ascending order.
+    pub fn set_status_cascade(
+        &mut self,
+        task_id: &TID,
+        status_flag: String,
+    ) -> Result<Vec<TID>, String> {
+        let plan = self.plan_status_cascade(task_id, &status_flag)?;
+        let mut affected = Vec::new();
+        for (affected_id, new_status) in plan {
+            (**self.tasks.get_mut(&affected_id).unwrap()).set_status(new_status);
+            affected.push(affected_id);
+        }
+        self.last_touched = Some(*task_id);
+        Ok(affected)
+    }
+
+    /// Get the new status of each task affected by `set_status_cascade`, in ascending order.
+    fn plan_status_cascade(
+        &self,
+        task_id: &TID,
+        status_flag: &str,
+    ) -> Result<Vec<(TID, TaskStatus)>, String> {
         self.check_task_exists(task_id)?;
-        let status = TaskStatus::from_status_flag(&status_flag)?;
-        if status != TaskStatus::Open && self.count_available_children(task_id) > 0 {
+        let status = self.workflow.parse_status(status_flag)?;
+        let done = self.workflow.is_done(&status);
+        let closing = done && self.workflow.needs_deps_done(&status);
+        if !closing {
+            self.check_deps_done(task_id, &status)?;
+        }
+        let mut affected = HashSet::new();
+        if closing {
+            // Close every dependency which isn't done yet.
+            self.collect_cascade(task_id, &self.children, false, &mut affected);
+        } else if !done {
+            // Reopen every dependent which was done on top of this task.
+            self.collect_cascade(task_id, &self.parents, true, &mut affected);
+        }
+        if self.tasks.get(task_id).unwrap().status != status {
+            affected.insert(*task_id);
+        }
+
+        let mut affected: Vec<TID> = affected.into_iter().collect();
+        affected.sort();
+        let mut plan = Vec::new();
+        for affected_id in affected {
+            let new_status = match affected_id == *task_id {
+                true => status.clone(),
+                false if closing => TaskStatus::Closed,
+                false => TaskStatus::Open,
+            };
+            let old_status = &self.tasks.get(&affected_id).unwrap().status;
+            if let Err(msg) = self.workflow.check_transition(old_status, &new_status) {
+                return Err(format!("Task {}: {}", bold_tid(affected_id), msg));
+            }
+            // Dependencies in other projects can't be closed along with the others.
+            let open_external = self.get_external_children(&affected_id).iter()
+                .find(|external_ref| self.external_done.get(*external_ref) == Some(&false));
+            if let (true, Some(external_ref)) = (closing, open_external) {
+                return Err(format!(
+                    "Task {}: depends on task {}, which isn't done",
+                    bold_tid(affected_id),
+                    bold_text(&external_ref.to_string()),
+                ));
+            }
+            plan.push((affected_id, new_status));
+        }
+        Ok(plan)
+    }
+
+    /// Check that a task can be given a status, i.e. that the status doesn't need the task's
+    /// dependencies to be done or that they are.
+    fn check_deps_done(&self, task_id: &TID, status: &TaskStatus) -> Result<(), String> {
+        if self.workflow.needs_deps_done(status) && self.count_available_children(task_id) > 0 {
             return Err(format!(
                 "Cannot set task {} as {}; the task has open dependencies",
                 bold_tid(*task_id),
                 bold_text(status.to_name()),
             ));
         }
-        (**self.tasks.get_mut(task_id).unwrap()).set_status(status);
         Ok(())
     }
 
+    /// Collect the tasks reachable from the given task through `edges`. If `only_done`, only
+    /// walks through tasks which were done on top of their dependencies (see
+    /// `is_completed_on_deps`); otherwise, only walks through tasks which aren't done.
+    fn collect_cascade(
+        &self,
+        task_id: &TID,
+        edges: &HashMap<TID, Vec<TID>>,
+        only_done: bool,
+        collected: &mut HashSet<TID>,
+    ) {
+        for next_id in edges.get(task_id).unwrap() {
+            let walk = match only_done {
+                true => self.is_completed_on_deps(next_id),
+                false => !self.is_done(next_id),
+            };
+            if !walk || collected.contains(next_id) {
+                continue;
+            }
+            collected.insert(*next_id);
+            self.collect_cascade(next_id, edges, only_done, collected);
+        }
+    }
+
+    /// Check whether a task is done with a status that needs its dependencies to be done, e.g.
+    /// closed but not cancelled.
+    fn is_completed_on_deps(&self, task_id: &TID) -> bool {
+        let status = &self.tasks.get(task_id).unwrap().status;
+        self.workflow.is_done(status) && self.workflow.needs_deps_done(status)
+    }
+
     pub fn get_status(&mut self, task_id: &TID) -> Result<TaskStatus, String> {
         self.check_task_exists(task_id)?;
-        Ok(self.tasks.get(task_id).unwrap().status)
+        Ok(self.tasks.get(task_id).unwrap().status.clone())
         
     }
 
+    /// Set the description of the task with the given TID, or remove it if None is given.
+    pub fn set_desc(&mut self, task_id: &TID, desc: Option<String>) -> Result<(), String> {
+        self.check_task_exists(task_id)?;
+        self.tasks.get_mut(task_id).unwrap().desc = desc;
+        self.last_touched = Some(*task_id);
+        Ok(())
+    }
+
+    /// Get the task with the given TID if it exists.
+    pub fn get_task(&self, task_id: &TID) -> Option<&Task> {
+        self.tasks.get(task_id).map(|task| &**task)
+    }
+
+    /// Get the TIDs of the tasks that the given task directly depends on.
+    pub fn get_children(&self, task_id: &TID) -> Option<&Vec<TID>> {
+        self.children.get(task_id)
+    }
+
+    /// Get the TIDs of the tasks that directly depend on the given task.
+    pub fn get_parents(&self, task_id: &TID) -> Option<&Vec<TID>> {
+        self.parents.get(task_id)
+    }
+
+    /// Get the TIDs of all tasks in the tree, in ascending order.
+    pub fn get_task_ids(&self) -> Vec<TID> {
+        let mut task_ids: Vec<TID> = self.tasks.keys().copied().collect();
+        task_ids.sort();
+        task_ids
+    }
+
+    /// Get the TIDs of the tasks which no other task depends on, in ascending order.
+    pub fn get_root_ids(&self) -> Vec<TID> {
+        self.get_task_ids()
+            .into_iter()
+            .filter(|task_id| self.parents.get(task_id).unwrap().is_empty())
+            .collect()
+    }
+
+    /// Check whether a task is available, i.e. it is not done and all of its dependencies are.
+    pub fn is_available(&self, task_id: &TID) -> bool {
+        self.tasks.contains_key(task_id)
+            && !self.is_done(task_id)
+            && self.count_available_children(task_id) == 0
+    }
+
+    /// Check whether a task's status counts as done in this tree's workflow.
+    pub fn is_done(&self, task_id: &TID) -> bool {
+        match self.tasks.get(task_id) {
+            Some(task) => self.workflow.is_done(&task.status),
+            None => false,
+        }
+    }
+
+    /// Count how many of the given tasks are completed, i.e. done with any status but cancelled.
+    /// Cancelled tasks are left out of the total.
+    pub fn count_completion(&self, task_ids: &[TID]) -> CompletionCounts {
+        let mut counts = CompletionCounts { completed: 0, total: 0, cancelled: 0 };
+        for task_id in task_ids {
+            let status = match self.tasks.get(task_id) {
+                Some(task) => &task.status,
+                None => continue,
+            };
+            if *status == TaskStatus::Cancelled {
+                counts.cancelled += 1;
+                continue;
+            }
+            counts.total += 1;
+            if self.workflow.is_done(status) {
+                counts.completed += 1;
+            }
+        }
+        counts
+    }
+
     /// Add the task with depends_on_id as a dependency for the task with task_id. Note that since
     /// we require the dependency graph to be acyclic, we throw an error if adding the dependency
     /// creates a cycle.
@@ -213,13 +842,12 @@ impl TaskTree {
                 bold_tid(*task_id)
             ));
         }
-        match self.children.get(task_id).unwrap().contains(depends_on_id) {
-            true => return Err(format!(
-                        "Task {} already depends on task {}.",
-                        bold_tid(*task_id),
-                        bold_tid(*depends_on_id)
-                    )),
-            false => ()
+        if self.children.get(task_id).unwrap().contains(depends_on_id) {
+            return Err(format!(
+                "Task {} already depends on task {}.",
+                bold_tid(*task_id),
+                bold_tid(*depends_on_id)
+            ));
         }
         
         if self.path_between(depends_on_id, task_id) {
@@ -233,9 +861,87 @@ impl TaskTree {
         this_children.push(*depends_on_id);
         let depends_on_parents = self.parents.get_mut(depends_on_id).unwrap();
         depends_on_parents.push(*task_id);
+        self.last_touched = Some(*task_id);
+        Ok(())
+    }
+
+    /// Add the task in another project referred to by depends_on as a dependency for the task with
+    /// task_id. The caller is responsible for checking that the other task exists and that this
+    /// doesn't create a cycle across projects, and should record its status with
+    /// `set_external_done`.
+    pub fn add_external_dependency(
+        &mut self,
+        task_id: &TID,
+        depends_on: ExternalRef,
+    ) -> Result<(), String> {
+        self.check_task_exists(task_id)?;
+        let external_children = self.external_children.entry(*task_id).or_default();
+        if external_children.contains(&depends_on) {
+            return Err(format!(
+                "Task {} already depends on task {}.",
+                bold_tid(*task_id),
+                bold_text(&depends_on.to_string()),
+            ));
+        }
+        external_children.push(depends_on);
+        self.last_touched = Some(*task_id);
+        Ok(())
+    }
+
+    /// Remove the task in another project referred to by depends_on as a dependency of task_id.
+    /// Returns error if task_id does not depend on it.
+    pub fn remove_external_dependency(
+        &mut self,
+        task_id: &TID,
+        depends_on: &ExternalRef,
+    ) -> Result<(), String> {
+        self.check_task_exists(task_id)?;
+        let external_children = self.external_children.entry(*task_id).or_default();
+        if !external_children.contains(depends_on) {
+            return Err(format!(
+                "Task {} does not depend on {}",
+                bold_tid(*task_id),
+                bold_text(&depends_on.to_string()),
+            ));
+        }
+        external_children.retain(|child| child != depends_on);
+        self.last_touched = Some(*task_id);
         Ok(())
     }
 
+    /// Get the tasks in other projects which the given task directly depends on.
+    pub fn get_external_children(&self, task_id: &TID) -> &[ExternalRef] {
+        match self.external_children.get(task_id) {
+            Some(external_children) => external_children,
+            None => &[],
+        }
+    }
+
+    /// Get every task in another project which this tree's tasks depend on.
+    pub fn get_all_external_refs(&self) -> HashSet<&ExternalRef> {
+        self.external_children.values().flatten().collect()
+    }
+
+    /// Get the tasks in other projects which the given task or any of its transitive dependencies
+    /// in this tree depend on.
+    pub fn get_external_dependencies(&self, task_id: &TID) -> Result<Vec<&ExternalRef>, String> {
+        let mut external_deps = Vec::new();
+        for subtree_id in self.get_subtree_ids(task_id)? {
+            for external_ref in self.get_external_children(&subtree_id) {
+                if !external_deps.contains(&external_ref) {
+                    external_deps.push(external_ref);
+                }
+            }
+        }
+        Ok(external_deps)
+    }
+
+    /// Record whether a task in another project which this tree's tasks depend on is done, as
+    /// decided by that project's workflow.
+    pub fn set_external_done(&mut self, external_ref: ExternalRef, done: bool) {
+        self.external_done.insert(external_ref, done);
+    }
+
     /// Adds a dependency between task_id and depends_on_id. Removes depends_on_id from task_id's
     /// dependencies, adds new_id to task_id's dependencies, adds depends_on_id to new_id's
     /// dependencies. Requires that task_id has depends_on_id as a dependency.
@@ -248,6 +954,7 @@ impl TaskTree {
         self.remove_dependency(task_id, depends_on_id)?;
         self.add_dependency(task_id, new_id)?;
         self.add_dependency(new_id, depends_on_id)?;
+        self.last_touched = Some(*new_id);
         Ok(())
     }
 
@@ -267,6 +974,7 @@ impl TaskTree {
         this_children.retain(|child_id| child_id != depends_on_id);
         let depends_on_parents = self.parents.get_mut(depends_on_id).unwrap();
         depends_on_parents.retain(|parent_id| parent_id != task_id);
+        self.last_touched = Some(*task_id);
         Ok(())
     }
 
@@ -280,11 +988,11 @@ impl TaskTree {
     ) -> Result<String, String> {
         let dep_ids = self.get_dependencies(task_id, opt_status_flag)?;
 
-        let mut res = String::from(format!("dependencies for task {}:", task_id));
+        let mut res = format!("dependencies for task {}:", task_id);
         for dep_id in dep_ids {
             let dep = self.tasks.get(dep_id).unwrap();
-            res.push_str(&*dep.get_repr());
-            res.push_str("\n");
+            res.push_str(&styled_repr(dep));
+            res.push('\n');
         }
         Ok(res)
     }
@@ -303,7 +1011,7 @@ impl TaskTree {
             Some(status_flag) => match &status_flag[..] {
                 "all" => (false, false, None),
                 _ => {
-                    let status = Some(TaskStatus::from_status_flag(&status_flag)?);
+                    let status = Some(self.workflow.parse_status(&status_flag)?);
                     (false, false, status)
                 }
             }
@@ -331,13 +1039,13 @@ impl TaskTree {
         if visited.contains(task_id) {
             return HashSet::new();
         } else {
-            visited.insert(task_id.clone());
+            visited.insert(*task_id);
         }
 
         // Get this task's dependencies.
         let this_children = self.children.get(task_id)
-            .expect(&format!("Task with ID {} does not exist.", task_id));
-        if this_children.len() == 0 {
+            .unwrap_or_else(|| panic!("Task with ID {} does not exist.", task_id));
+        if this_children.is_empty() {
             return HashSet::new();
         }
 
@@ -351,18 +1059,18 @@ impl TaskTree {
                 leaf = leaf || num_available_children == 0;
                 
             }
-            let closed = self.tasks.get(child_id).unwrap().status == TaskStatus::Closed;
+            let closed = self.is_done(child_id);
 
             // add this child to the results if:
             //  1) either the child is a leaf, or we want all tasks, and
             //  2) the child is available (not complete), or we don't want only available tasks.
             if (leaf || !only_leaves) && (!closed || !only_available) {
-                match status_filter {
+                match &status_filter {
                     None => {
                         to_return.insert(child_id);
                     },
                     Some(status) => {
-                        if status == self.tasks.get(child_id).unwrap().status {
+                        if *status == self.tasks.get(child_id).unwrap().status {
                             to_return.insert(child_id);
                         }
                     },
@@ -375,7 +1083,7 @@ impl TaskTree {
                     child_id,
                     only_leaves,
                     only_available,
-                    status_filter,
+                    status_filter.clone(),
                     visited
                 ));
             }
@@ -384,6 +1092,35 @@ impl TaskTree {
         to_return
     }
 
+    /// Find redundant dependencies, i.e. dependencies of a task on another task which it already
+    /// depends on through one of its other dependencies. Returns (task, dependency) pairs in
+    /// ascending order.
+    pub fn find_redundant_dependencies(&self) -> Vec<(TID, TID)> {
+        let mut redundant = Vec::new();
+        for task_id in self.get_task_ids() {
+            let children = self.children.get(&task_id).unwrap();
+            for child_id in children {
+                let implied = children.iter()
+                    .any(|other_id| other_id != child_id && self.path_between(other_id, child_id));
+                if implied {
+                    redundant.push((task_id, *child_id));
+                }
+            }
+        }
+        redundant.sort();
+        redundant
+    }
+
+    /// Remove every redundant dependency (see `find_redundant_dependencies`). This doesn't change
+    /// which tasks each task transitively depends on. Returns the removed (task, dependency) pairs.
+    pub fn remove_redundant_dependencies(&mut self) -> Result<Vec<(TID, TID)>, String> {
+        let redundant = self.find_redundant_dependencies();
+        for (task_id, child_id) in &redundant {
+            self.remove_dependency(task_id, child_id)?;
+        }
+        Ok(redundant)
+    }
+
     /// Ensure that adding a dependency does not create a cycle.
     fn path_between(&self, u: &TID, v: &TID) -> bool {
         if u == v {
@@ -402,7 +1139,13 @@ impl TaskTree {
         let children = self.children.get(task_id).unwrap();
         let mut num_available = 0;
         for id in children {
-            if self.tasks.get(&id).unwrap().status != TaskStatus::Closed {
+            if !self.is_done(id) {
+                num_available += 1;
+            }
+        }
+        // Dependencies on tasks in other projects whose status is unknown don't block this task.
+        for external_ref in self.get_external_children(task_id) {
+            if self.external_done.get(external_ref) == Some(&false) {
                 num_available += 1;
             }
         }
@@ -424,10 +1167,10 @@ impl TaskTree {
         let mut result = Vec::new();
         
         for task_id in self.tasks.keys() {
-            let task = self.tasks.get(&task_id).unwrap();
+            let task = self.tasks.get(task_id).unwrap();
             let num_available_children = self.count_available_children(task_id);
             let leaf = num_available_children == 0;
-            let not_closed = task.status != TaskStatus::Closed;
+            let not_closed = !self.workflow.is_done(&task.status);
             if leaf && not_closed {
                 result.push(&**task);
             }
@@ -438,33 +1181,129 @@ impl TaskTree {
 }
 
 
-#[derive(PartialEq, Serialize, Deserialize, Debug, Copy, Clone)]
+/// How many of a set of tasks are completed. Cancelled tasks are counted separately, and are not
+/// part of the total.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct CompletionCounts {
+    pub completed: usize,
+    pub total: usize,
+    pub cancelled: usize,
+}
+
+
+/// Turn a task's name into a slug, e.g. "Build the API!" into "build-the-api".
+fn get_slug(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+/// Check whether the characters of `query` appear in `text` in the same order.
+fn is_subsequence(query: &str, text: &str) -> bool {
+    let mut text_chars = text.chars();
+    query.chars().all(|query_char| text_chars.any(|text_char| text_char == query_char))
+}
+
+
+/// A reference to a task in another project, written as "project:task_id".
+#[derive(PartialEq, Eq, Hash, Serialize, Deserialize, Debug, Clone)]
+pub struct ExternalRef {
+    pub project: String,
+    pub task_id: TID,
+}
+
+impl ExternalRef {
+
+    /// Parse a "project:task_id" reference. Returns None if the argument isn't of this form.
+    pub fn parse(arg: &str) -> Option<Self> {
+        let (project, task_id) = arg.rsplit_once(':')?;
+        if project.is_empty() {
+            return None;
+        }
+        match task_id.parse() {
+            Ok(task_id) => Some(ExternalRef { project: project.to_string(), task_id }),
+            _ => None,
+        }
+    }
+
+}
+
+impl fmt::Display for ExternalRef {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.project, self.task_id)
+    }
+
+}
+
+
+/// What to do when a task is reopened while closed tasks still depend on it.
+#[derive(PartialEq, Serialize, Deserialize, Debug, Copy, Clone, Default)]
+pub enum ReopenPolicy {
+    /// Refuse to reopen the task.
+    Reject,
+    /// Reopen the task, but leave its dependents closed.
+    #[default]
+    Warn,
+    /// Reopen the task along with every dependent that was closed on top of it.
+    Reopen,
+}
+
+impl ReopenPolicy {
+
+    pub fn from_policy_flag(policy_flag: &str) -> Result<Self, String> {
+        match policy_flag {
+            "reject" => Ok(Self::Reject),
+            "warn" => Ok(Self::Warn),
+            "reopen" => Ok(Self::Reopen),
+            _ => Err(format!("No such reopen policy {}", bold_text(policy_flag))),
+        }
+    }
+
+    pub fn to_name(&self) -> &str {
+        match self {
+            Self::Reject => "reject",
+            Self::Warn => "warn",
+            Self::Reopen => "reopen",
+        }
+    }
+
+}
+
+
+/// A task's status. Besides the built-in statuses, a project's workflow can define custom ones.
+#[derive(PartialEq, Serialize, Deserialize, Debug, Clone)]
 pub enum TaskStatus {
     Open,
     InProgress,
     Closed,
+    /// Dropped rather than completed. Cancelled tasks don't block the tasks depending on them,
+    /// but don't count as completed either.
+    Cancelled,
+    Custom(String),
 }
 
 impl TaskStatus {
 
-    fn from_status_flag(status_flag: &str) -> Result<Self, String> {
-        match status_flag {
-            "open" => Ok(Self::Open),
-            "in-progress" => Ok(Self::InProgress),
-            "closed" => Ok(Self::Closed),
-            _ => Err(format!("No such status {}", bold_text(status_flag)))
+    /// Parse a built-in status flag. Use `TaskTree::parse_status` to also accept the custom
+    /// statuses of a project's workflow.
+    pub fn from_status_flag(status_flag: &str) -> Result<Self, String> {
+        match Self::from_name(status_flag) {
+            Self::Custom(_) => Err(format!("No such status {}", bold_text(status_flag))),
+            status => Ok(status),
         }
     }
 
-    fn from_opt_status_flag(opt_status_flag: Option<String>) -> Result<Option<Self>, String> {
-        match opt_status_flag {
-            Some(status_flag) => {
-                match Self::from_status_flag(&status_flag) {
-                    Ok(status) => Ok(Some(status)),
-                    Err(msg) => Err(msg),
-                }
-            },
-            None => Ok(None)
+    /// Get the status with the given name, which is custom unless it's one of the built-in ones.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "open" => Self::Open,
+            "in-progress" => Self::InProgress,
+            "closed" => Self::Closed,
+            "cancelled" => Self::Cancelled,
+            _ => Self::Custom(name.to_string()),
         }
     }
 
@@ -473,35 +1312,74 @@ impl TaskStatus {
             Self::Open => "open",
             Self::InProgress => "in-progress",
             Self::Closed => "closed",
+            Self::Cancelled => "cancelled",
+            Self::Custom(name) => name,
         }
     }
 
 }
 
-impl ToString for TaskStatus {
-        
-    fn to_string(&self) -> String {
-        match self {
-            TaskStatus::Open => String::from("[O]"),
-            TaskStatus::InProgress => String::from("[I]"),
-            TaskStatus::Closed => String::from("[C]"),
-        }
+impl fmt::Display for TaskStatus {
+
+    /// Display the status as a marker, e.g. "[O]" for open. Custom statuses use the initials of
+    /// their name, e.g. "[IR]" for in-review.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let marker = match self {
+            TaskStatus::Open => "[O]".to_string(),
+            TaskStatus::InProgress => "[I]".to_string(),
+            TaskStatus::Closed => "[C]".to_string(),
+            TaskStatus::Cancelled => "[X]".to_string(),
+            TaskStatus::Custom(name) => {
+                let initials: String = name.split('-')
+                    .filter_map(|word| word.chars().next())
+                    .collect();
+                format!("[{}]", initials.to_uppercase())
+            },
+        };
+        write!(f, "{}", marker)
     }
- 
+
 }
 
 
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Task {
-    created_timestamp: String,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    created_timestamp: DateTime<Utc>,
     name: String,
     desc: Option<String>,
     id: TID,
     status: TaskStatus,
+    /// Every status the task has had, starting with open when it was created. Tasks created
+    /// before this was recorded only have the changes made since.
+    #[serde(default)]
+    status_history: Vec<StatusChange>,
     repr: String,
 }
 
+
+/// Deserialize a tree's tasks, migrating the statuses of projects which defined a custom cancelled
+/// status before it was built in.
+fn deserialize_tasks<'de, D>(deserializer: D) -> Result<HashMap<TID, Box<Task>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut tasks = HashMap::<TID, Box<Task>>::deserialize(deserializer)?;
+    for task in tasks.values_mut() {
+        task.migrate_statuses();
+    }
+    Ok(tasks)
+}
+
+
+/// A change of a task's status, and when it happened.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct StatusChange {
+    pub status: TaskStatus,
+    pub timestamp: DateTime<Utc>,
+}
+
 impl Task {
 
     pub fn new(id: TID, name: String, desc: Option<String>) -> Self {
@@ -509,17 +1387,26 @@ impl Task {
         let cur_time: DateTime<Utc> = Utc::now();
 
         let mut new_task = Task {
-            created_timestamp: cur_time.format(DATE_FORMAT).to_string(),
+            created_timestamp: cur_time,
             id,
             repr: String::new(),
             desc,
             name,
+            status_history: vec![StatusChange { status: status.clone(), timestamp: cur_time }],
             status,
         };
         new_task.update_repr();
         new_task
     }
 
+    /// Get a copy of this task with a different TID.
+    pub fn with_id(&self, id: TID) -> Self {
+        let mut new_task = self.clone();
+        new_task.id = id;
+        new_task.update_repr();
+        new_task
+    }
+
     pub fn get_id(&self) -> &TID {
         &self.id
     }
@@ -530,12 +1417,12 @@ impl Task {
 
     pub fn get_desc(&self) -> Option<&str> {
         match &self.desc {
-            Some(description) => Some(&description),
+            Some(description) => Some(description),
             None => None,
         }
     }
 
-    pub fn get_created_timestamp(&self) -> &str {
+    pub fn get_created_timestamp(&self) -> &DateTime<Utc> {
         &self.created_timestamp
     }
 
@@ -547,15 +1434,55 @@ impl Task {
         &self.repr
     }
 
+    pub fn get_status_history(&self) -> &[StatusChange] {
+        &self.status_history
+    }
+
+    /// Get this task's status as of the given time, or None if it didn't exist yet. Tasks without
+    /// any recorded status changes are assumed to always have had their current status.
+    pub fn get_status_at(&self, timestamp: &DateTime<Utc>) -> Option<&TaskStatus> {
+        if self.created_timestamp > *timestamp {
+            return None;
+        }
+        match self.status_history.iter().rev().find(|change| change.timestamp <= *timestamp) {
+            Some(change) => Some(&change.status),
+            None => match self.status_history.first() {
+                // Tasks created before status changes were recorded were open until the first
+                // recorded change.
+                Some(_) => Some(&TaskStatus::Open),
+                None => Some(&self.status),
+            },
+        }
+    }
+
+    /// Set this task's status, recording the change in its status history.
     pub fn set_status(&mut self, new_status: TaskStatus) {
+        self.set_status_at(new_status, Utc::now());
+    }
+
+    /// Set this task's status as of the given time, recording the change in its status history.
+    pub fn set_status_at(&mut self, new_status: TaskStatus, timestamp: DateTime<Utc>) {
+        if new_status != self.status {
+            self.status_history.push(StatusChange { status: new_status.clone(), timestamp });
+        }
         self.status = new_status;
         self.update_repr();
     }
 
+    /// Replace custom statuses which have since become built-in ones (i.e. cancelled), in the
+    /// status and its history.
+    fn migrate_statuses(&mut self) {
+        self.status = TaskStatus::from_name(self.status.to_name());
+        for change in &mut self.status_history {
+            change.status = TaskStatus::from_name(change.status.to_name());
+        }
+        self.update_repr();
+    }
+
     /// We cache the repr for searching.
     fn update_repr(&mut self) {
         let status_str = format!("{} {: >5}", &self.status.to_string(), &self.id);
-        let mut new_repr = String::from(status_str);
+        let mut new_repr = status_str;
         new_repr.push_str(&format!(": {}", &self.name));
         self.repr = new_repr
     }
@@ -568,9 +1495,20 @@ impl Task {
 pub mod tests {
     use super::*;
 
+    /// Set a task's status as of the given time, bypassing the tree's checks.
+    pub fn set_status_at(
+        tree: &mut TaskTree,
+        task_id: TID,
+        status_flag: &str,
+        timestamp: DateTime<Utc>,
+    ) {
+        let status = tree.parse_status(status_flag).unwrap();
+        tree.tasks.get_mut(&task_id).unwrap().set_status_at(status, timestamp);
+    }
+
     fn get_task_reprs(tree: &TaskTree) -> Vec<&String> {
         let mut reprs = Vec::new();
-        for task in tree.tasks.values().into_iter() {
+        for task in tree.tasks.values() {
             reprs.push(task.get_repr());
         }
         reprs
@@ -585,9 +1523,8 @@ pub mod tests {
     }
 
     fn has_dependency(tree: &TaskTree, task_id: &TID, depends_on_id: &TID) -> bool {
-        let task_dependencies = tree.children.get(task_id).expect(
-                &format!("Task with ID {} not found.", task_id)
-        );
+        let task_dependencies = tree.children.get(task_id)
+            .unwrap_or_else(|| panic!("Task with ID {} not found.", task_id));
         for &id in task_dependencies {
             if id == *depends_on_id {
                 return true;
@@ -597,9 +1534,8 @@ pub mod tests {
     }
 
     fn has_parent(tree: &TaskTree, task_id: &TID, parent_id: &TID) -> bool {
-        let task_parents = tree.parents.get(task_id).expect(
-                &format!("Task with ID {} not found.", task_id)
-        );
+        let task_parents = tree.parents.get(task_id)
+            .unwrap_or_else(|| panic!("Task with ID {} not found.", task_id));
 
         for &id in task_parents {
             if id == *parent_id {
@@ -694,15 +1630,13 @@ pub mod tests {
         let tid6: TID = 6;
         tree.set_status(&tid6, "closed".to_string()).unwrap();
 
-        let expect_tasks = vec![
-            "[O]     1: Task 1",
+        let expect_tasks = ["[O]     1: Task 1",
             "[O]     2: Task 2",
             "[O]     3: Task 3",
             "[O]     4: Task 4",
             "[O]     5: Task 5",
             "[C]     6: Task 6",
-            "[O]     7: Task 7",
-        ];
+            "[O]     7: Task 7"];
 
         let tasks = get_task_reprs(&tree);
 
@@ -718,27 +1652,23 @@ pub mod tests {
         let tid6: TID = 6;
         tree.set_status(&tid6, "closed".to_string()).unwrap();
 
-        let expect_matches = vec![
-            "[C]     6: Task 6",
-        ];
+        let expect_matches = ["[C]     6: Task 6"];
         let matches = tree.search_tasks("[C]", None).unwrap();
         for _match in &matches {
-            assert!(expect_matches.contains(&&_match[..]));
+            assert!(expect_matches.contains(&&_match.get_repr()[..]));
         }
         assert!(expect_matches.len() == matches.len());
 
-        let expect_matches = vec![
-            "[O]     1: Task 1",
+        let expect_matches = ["[O]     1: Task 1",
             "[O]     2: Task 2",
             "[O]     3: Task 3",
             "[O]     4: Task 4",
             "[O]     5: Task 5",
             "[C]     6: Task 6",
-            "[O]     7: Task 7",
-        ];
+            "[O]     7: Task 7"];
         let matches = tree.search_tasks("Task", None).unwrap();
         for _match in &matches {
-            assert!(expect_matches.contains(&&_match[..]));
+            assert!(expect_matches.contains(&&_match.get_repr()[..]));
         }
         assert!(expect_matches.len() == matches.len());
     }
@@ -827,7 +1757,7 @@ pub mod tests {
         let tid2: TID = 2;
         let tid7: TID = 7;
 
-        let expect_matches = vec![tid2, tid7];
+        let expect_matches = [tid2, tid7];
         let matches = get_children_for(&tree, &tid1);
         for _match in matches.iter() {
             assert!(expect_matches.contains(_match));
@@ -843,14 +1773,14 @@ pub mod tests {
         let tid4: TID = 4;
         let tid5: TID = 5;
 
-        let expect_matches = vec![tid3, tid4];
+        let expect_matches = [tid3, tid4];
         let matches = get_parents_for(&tree, &tid5);
         for _match in matches.iter() {
             assert!(expect_matches.contains(_match));
         }
         assert!(expect_matches.len() == matches.len());
 
-        let expect_matches = vec![tid2];
+        let expect_matches = [tid2];
         let matches = get_parents_for(&tree, &tid4);
         for _match in matches.iter() {
             assert!(expect_matches.contains(_match));
@@ -941,4 +1871,409 @@ pub mod tests {
         assert!(deps.contains(&&tid6));
         assert!(deps.contains(&&tid7));
     }
+
+    #[test]
+    fn test_set_status_cascade_close() {
+        let mut tree = setup_tree();
+        let tid2: TID = 2;
+        let tid6: TID = 6;
+        tree.set_status(&tid6, "closed".to_string()).unwrap();
+
+        let preview = tree.preview_status_cascade(&tid2, "closed").unwrap();
+        assert_eq!(preview, vec![2, 3, 4, 5]);
+        assert!(tree.tasks.values().all(|task| task.id == tid6 || task.status == TaskStatus::Open));
+
+        let affected = tree.set_status_cascade(&tid2, "closed".to_string()).unwrap();
+        assert_eq!(affected, preview);
+        for tid in [2, 3, 4, 5, 6] {
+            assert!(tree.tasks.get(&tid).unwrap().status == TaskStatus::Closed);
+        }
+        assert!(tree.tasks.get(&1).unwrap().status == TaskStatus::Open);
+    }
+
+    #[test]
+    fn test_set_status_cascade_reopen() {
+        let mut tree = setup_tree();
+        let tid1: TID = 1;
+        let tid6: TID = 6;
+        tree.set_status_cascade(&tid1, "closed".to_string()).unwrap();
+
+        // Only the dependents closed on top of task 6 are reopened.
+        let affected = tree.set_status_cascade(&tid6, "in-progress".to_string()).unwrap();
+        assert_eq!(affected, vec![1, 2, 4, 6, 7]);
+        assert!(tree.tasks.get(&tid6).unwrap().status == TaskStatus::InProgress);
+        assert!(tree.tasks.get(&4).unwrap().status == TaskStatus::Open);
+        assert!(tree.tasks.get(&3).unwrap().status == TaskStatus::Closed);
+        assert!(tree.tasks.get(&5).unwrap().status == TaskStatus::Closed);
+    }
+
+    #[test]
+    fn test_set_status_cascade_open_dependencies() {
+        let mut tree = setup_tree();
+        let tid4: TID = 4;
+        assert!(tree.preview_status_cascade(&tid4, "in-progress").is_err());
+        assert!(tree.set_status_cascade(&tid4, "in-progress".to_string()).is_err());
+        assert!(tree.tasks.get(&tid4).unwrap().status == TaskStatus::Open);
+
+        tree.set_status_cascade(&tid4, "closed".to_string()).unwrap();
+        tree.set_status(&6, "open".to_string()).unwrap();
+        let err = tree.set_status_cascade(&tid4, "in-progress".to_string()).unwrap_err();
+        assert!(err.contains("open dependencies"));
+        assert!(tree.tasks.get(&tid4).unwrap().status == TaskStatus::Closed);
+    }
+
+    #[test]
+    fn test_set_status_cascade_open_external_dependency() {
+        let mut tree = setup_tree();
+        let other = ExternalRef { project: "other".to_string(), task_id: 2 };
+        tree.add_external_dependency(&5, other.clone()).unwrap();
+        tree.set_external_done(other.clone(), false);
+
+        // Task 5 can't be closed along with the other dependencies of task 3.
+        let err = tree.set_status_cascade(&3, "closed".to_string()).unwrap_err();
+        assert!(err.contains("other:2"));
+        assert!(tree.preview_status_cascade(&3, "closed").is_err());
+        assert!(tree.tasks.values().all(|task| task.status == TaskStatus::Open));
+
+        tree.set_external_done(other, true);
+        assert_eq!(tree.set_status_cascade(&3, "closed".to_string()).unwrap(), vec![3, 5]);
+    }
+
+    #[test]
+    fn test_reopen_policy() {
+        let mut tree = setup_tree();
+        let tid4: TID = 4;
+        tree.set_status_cascade(&tid4, "closed".to_string()).unwrap();
+        tree.set_status(&3, "closed".to_string()).unwrap();
+        tree.set_status(&2, "closed".to_string()).unwrap();
+        assert!(tree.find_inconsistencies().is_empty());
+
+        tree.set_reopen_policy(ReopenPolicy::Reject);
+        assert!(tree.set_status(&5, "open".to_string()).is_err());
+        assert!(tree.tasks.get(&5).unwrap().status == TaskStatus::Closed);
+
+        tree.set_reopen_policy(ReopenPolicy::Warn);
+        assert_eq!(tree.set_status(&5, "open".to_string()).unwrap(), vec![2, 3, 4]);
+        assert_eq!(tree.find_inconsistencies(), vec![(3, 5), (4, 5)]);
+
+        tree.set_status(&5, "closed".to_string()).unwrap();
+        tree.set_reopen_policy(ReopenPolicy::Reopen);
+        assert_eq!(tree.set_status(&5, "in-progress".to_string()).unwrap(), vec![2, 3, 4]);
+        for tid in [2, 3, 4] {
+            assert!(tree.tasks.get(&tid).unwrap().status == TaskStatus::Open);
+        }
+        assert!(tree.find_inconsistencies().is_empty());
+    }
+
+    #[test]
+    fn test_reopen_policy_transitions() {
+        let mut tree = setup_tree();
+        tree.get_workflow_mut().add_state("shipped", true, true).unwrap();
+        tree.set_status(&5, "closed".to_string()).unwrap();
+        tree.set_status(&3, "shipped".to_string()).unwrap();
+        tree.get_workflow_mut()
+            .set_transitions("shipped", Some(vec!["cancelled".to_string()]))
+            .unwrap();
+        tree.set_reopen_policy(ReopenPolicy::Reopen);
+
+        // Task 3 can't be reopened, so task 5 isn't either.
+        let err = tree.set_status(&5, "open".to_string()).unwrap_err();
+        assert!(err.starts_with(&format!("Task {}", bold_tid(3))));
+        assert!(tree.tasks.get(&3).unwrap().status == TaskStatus::Custom("shipped".to_string()));
+        assert!(tree.tasks.get(&5).unwrap().status == TaskStatus::Closed);
+    }
+
+    #[test]
+    fn test_custom_workflow() {
+        let mut tree = setup_tree();
+        tree.get_workflow_mut().add_state("wont-fix", true, false).unwrap();
+        tree.get_workflow_mut().add_state("in-review", false, true).unwrap();
+        assert!(tree.set_status(&5, "blocked".to_string()).is_err());
+
+        // A done custom status unblocks the tasks depending on it, without needing its own
+        // dependencies to be done.
+        tree.set_status(&4, "wont-fix".to_string()).unwrap();
+        assert!(tree.is_done(&4));
+        assert_eq!(tree.tasks.get(&4).unwrap().get_repr(), "[WF]     4: Task 4");
+        assert!(tree.set_status(&3, "in-review".to_string()).is_err());
+        tree.set_status(&5, "closed".to_string()).unwrap();
+        tree.set_status(&3, "in-review".to_string()).unwrap();
+        assert!(!tree.is_available(&2));
+
+        tree.get_workflow_mut()
+            .set_transitions("in-review", Some(vec!["closed".to_string()]))
+            .unwrap();
+        assert!(tree.set_status(&3, "open".to_string()).is_err());
+        tree.set_status(&3, "closed".to_string()).unwrap();
+        assert!(tree.is_available(&2));
+
+        assert!(tree.remove_workflow_state("wont-fix").is_err());
+        tree.set_status(&4, "open".to_string()).unwrap();
+        tree.remove_workflow_state("wont-fix").unwrap();
+        assert!(tree.parse_status("wont-fix").is_err());
+    }
+
+    #[test]
+    fn test_cancelled_status() {
+        let mut tree = setup_tree();
+        // Cancelling doesn't need the task's dependencies to be done, and doesn't close them.
+        assert_eq!(tree.set_status_cascade(&4, "cancelled".to_string()).unwrap(), vec![4]);
+        assert_eq!(tree.tasks.get(&4).unwrap().get_repr(), "[X]     4: Task 4");
+        tree.set_status(&5, "closed".to_string()).unwrap();
+        tree.set_status(&3, "closed".to_string()).unwrap();
+        assert!(tree.is_available(&2));
+        assert!(tree.find_inconsistencies().is_empty());
+        tree.set_status(&2, "closed".to_string()).unwrap();
+
+        // Cancelled dependents aren't reopened along with their dependencies.
+        tree.set_reopen_policy(ReopenPolicy::Reopen);
+        assert_eq!(tree.set_status(&6, "in-progress".to_string()).unwrap(), Vec::<TID>::new());
+        assert!(tree.tasks.get(&4).unwrap().status == TaskStatus::Cancelled);
+
+        let counts = tree.count_completion(&tree.get_task_ids());
+        assert_eq!(counts, CompletionCounts { completed: 3, total: 6, cancelled: 1 });
+    }
+
+    #[test]
+    fn test_custom_cancelled_status_migration() {
+        let mut tree = setup_tree();
+        tree.tasks.get_mut(&6).unwrap().set_status(TaskStatus::Custom("cancelled".to_string()));
+        let mut value = serde_json::to_value(&tree).unwrap();
+        for state in value["workflow"]["states"].as_array_mut().unwrap() {
+            if state["name"] == "cancelled" {
+                state["needs_deps_done"] = serde_json::Value::Bool(true);
+            }
+        }
+
+        // Saved before cancelled was built in, with a custom cancelled status.
+        let tree: TaskTree = serde_json::from_value(value).unwrap();
+        let task = tree.tasks.get(&6).unwrap();
+        assert!(task.status == TaskStatus::Cancelled);
+        assert!(task.status_history.last().unwrap().status == TaskStatus::Cancelled);
+        assert_eq!(task.get_repr(), "[X]     6: Task 6");
+        assert_eq!(tree.view_tasks(Some("cancelled".to_string())).unwrap().len(), 1);
+        let workflow = tree.get_workflow();
+        let cancelled_states = workflow.get_states().iter()
+            .filter(|state| state.get_name() == "cancelled")
+            .count();
+        assert_eq!(cancelled_states, 1);
+        assert!(!workflow.needs_deps_done(&TaskStatus::Cancelled));
+    }
+
+    #[test]
+    fn test_remove_task_splice() {
+        let mut tree = setup_tree();
+        let tid2: TID = 2;
+        let tid1: TID = 1;
+
+        assert_eq!(tree.preview_splice(&tid2).unwrap(), vec![(1, 3), (1, 4)]);
+        tree.remove_task_splice(&tid2).unwrap();
+
+        assert!(!tree.tasks.contains_key(&tid2));
+        let tid1_children = get_children_for(&tree, &tid1);
+        assert!(tid1_children.contains(&3));
+        assert!(tid1_children.contains(&4));
+        assert!(tid1_children.contains(&7));
+        assert!(has_parent(&tree, &3, &tid1));
+
+        // Existing dependencies are not duplicated.
+        tree.add_dependency(&4, &3).unwrap();
+        tree.add_dependency(&7, &3).unwrap();
+        assert_eq!(tree.remove_task_splice(&3).unwrap(), vec![(1, 5), (7, 5)]);
+        assert_eq!(get_children_for(&tree, &4).iter().filter(|id| **id == 5).count(), 1);
+    }
+
+    #[test]
+    fn test_remove_task_recursive() {
+        let mut tree = setup_tree();
+        let tid2: TID = 2;
+
+        // Task 6 is still needed by task 7.
+        assert_eq!(tree.preview_remove_recursive(&tid2).unwrap(), vec![2, 3, 4, 5]);
+        assert_eq!(tree.remove_task_recursive(&tid2).unwrap(), vec![2, 3, 4, 5]);
+        assert!(tree.tasks.contains_key(&6));
+        assert_eq!(get_parents_for(&tree, &6), &vec![7]);
+        assert_eq!(get_children_for(&tree, &1), &vec![7]);
+
+        assert_eq!(tree.remove_task_recursive(&1).unwrap(), vec![1, 6, 7]);
+        assert!(tree.tasks.is_empty());
+    }
+
+    #[test]
+    fn test_copy_subgraph_to() {
+        let tree = setup_tree();
+        let mut target = TaskTree::new();
+        target.add_task("Existing".to_string(), None);
+
+        let id_map = tree.copy_subgraph_to(&4, true, &mut target, "src").unwrap();
+        assert_eq!(id_map, HashMap::from([(4, 2), (5, 3), (6, 4)]));
+        assert_eq!(target.tasks.get(&2).unwrap().get_repr(), "[O]     2: Task 4");
+        assert!(has_dependency(&target, &2, &3));
+        assert!(has_dependency(&target, &2, &4));
+        assert!(has_parent(&target, &4, &2));
+        assert_eq!(target.id_counter, 5);
+
+        let id_map = tree.copy_subgraph_to(&4, false, &mut target, "src").unwrap();
+        assert_eq!(id_map, HashMap::from([(4, 5)]));
+        assert!(get_children_for(&target, &5).is_empty());
+        let left_behind: Vec<String> = target.get_external_children(&5).iter()
+            .map(|external_ref| external_ref.to_string())
+            .collect();
+        assert_eq!(left_behind, vec!["src:5", "src:6"]);
+        assert_eq!(tree.tasks.len(), 7);
+    }
+
+    #[test]
+    fn test_move_subgraph_to() {
+        let mut tree = setup_tree();
+        let mut target = TaskTree::new();
+
+        // Task 6 is still needed by task 7, so it is copied rather than moved.
+        let id_map = tree.move_subgraph_to(&4, true, &mut target, "src", "other").unwrap();
+        assert_eq!(id_map.len(), 3);
+        assert_eq!(target.tasks.len(), 3);
+        assert!(!tree.tasks.contains_key(&4));
+        assert!(tree.tasks.contains_key(&5));
+        assert!(tree.tasks.contains_key(&6));
+        assert!(!has_dependency(&tree, &2, &4));
+
+        let id_map = tree.move_subgraph_to(&7, false, &mut target, "src", "other").unwrap();
+        assert_eq!(id_map, HashMap::from([(7, 4)]));
+        assert!(!tree.tasks.contains_key(&7));
+        assert!(get_parents_for(&tree, &6).is_empty());
+
+        // The moved task still depends on task 6, which was left behind.
+        let left_behind = ExternalRef { project: "src".to_string(), task_id: 6 };
+        assert_eq!(target.get_external_children(&4).to_vec(), vec![left_behind.clone()]);
+        assert!(!target.is_available(&4));
+    }
+
+    #[test]
+    fn test_move_subgraph_keeps_dependents() {
+        let mut tree = setup_tree();
+        let mut target = TaskTree::new();
+        let tid2: TID = 2;
+        let id_map = tree.move_subgraph_to(&4, false, &mut target, "src", "other").unwrap();
+
+        // Task 2 depends on the moved task in the other project instead.
+        let moved = ExternalRef { project: "other".to_string(), task_id: id_map[&4] };
+        assert_eq!(tree.get_external_children(&tid2).to_vec(), vec![moved.clone()]);
+        tree.set_status(&5, "closed".to_string()).unwrap();
+        tree.set_status(&3, "closed".to_string()).unwrap();
+        assert!(!tree.is_available(&tid2));
+        tree.set_external_done(moved, true);
+        assert!(tree.is_available(&tid2));
+    }
+
+    #[test]
+    fn test_external_dependencies() {
+        let mut tree = setup_tree();
+        let tid5: TID = 5;
+        let backend = ExternalRef::parse("backend:3").unwrap();
+        assert_eq!(backend, ExternalRef { project: "backend".to_string(), task_id: 3 });
+        assert!(ExternalRef::parse("3").is_none());
+        assert!(ExternalRef::parse("backend:x").is_none());
+
+        tree.add_external_dependency(&tid5, backend.clone()).unwrap();
+        assert!(tree.add_external_dependency(&tid5, backend.clone()).is_err());
+        assert_eq!(tree.get_external_dependencies(&2).unwrap(), vec![&backend]);
+
+        // Unknown external tasks don't block; open ones do.
+        assert!(tree.is_available(&tid5));
+        tree.set_external_done(backend.clone(), false);
+        assert!(!tree.is_available(&tid5));
+        assert!(tree.set_status(&tid5, "closed".to_string()).is_err());
+        tree.set_external_done(backend.clone(), true);
+        tree.set_status(&tid5, "closed".to_string()).unwrap();
+
+        tree.remove_external_dependency(&tid5, &backend).unwrap();
+        assert!(tree.get_external_children(&tid5).is_empty());
+        assert!(tree.remove_external_dependency(&tid5, &backend).is_err());
+    }
+
+    #[test]
+    fn test_merge_tasks() {
+        let mut tree = setup_tree();
+        let tid3: TID = 3;
+        let tid7: TID = 7;
+        tree.tasks.get_mut(&tid3).unwrap().desc = Some("first".to_string());
+        tree.tasks.get_mut(&tid7).unwrap().desc = Some("second".to_string());
+
+        tree.merge_tasks(&tid3, &tid7).unwrap();
+        assert!(!tree.tasks.contains_key(&tid7));
+        assert!(has_dependency(&tree, &1, &tid3));
+        assert!(has_dependency(&tree, &tid3, &5));
+        assert!(has_dependency(&tree, &tid3, &6));
+        assert!(has_parent(&tree, &6, &tid3));
+        assert_eq!(tree.tasks.get(&tid3).unwrap().get_desc(), Some("first\n\nsecond"));
+
+        // Merging task 2 into task 5 would make task 5 depend on itself through task 3.
+        let before = tree.clone();
+        assert!(tree.merge_tasks(&5, &2).is_err());
+        assert_eq!(tree, before);
+
+        // A direct dependency between the two tasks is dropped.
+        tree.merge_tasks(&4, &5).unwrap();
+        assert!(has_dependency(&tree, &tid3, &4));
+        assert!(!get_children_for(&tree, &4).contains(&4));
+    }
+
+    #[test]
+    fn test_split_task() {
+        let mut tree = setup_tree();
+        let tid4: TID = 4;
+        tree.set_status_cascade(&6, "closed".to_string()).unwrap();
+
+        let new_ids = tree.split_task(&tid4, vec!["Part A".to_string(), "Part B".to_string()])
+            .unwrap();
+        assert_eq!(new_ids, vec![8, 9]);
+        assert!(!tree.tasks.contains_key(&tid4));
+        for new_id in &new_ids {
+            assert!(has_dependency(&tree, &2, new_id));
+            assert!(has_dependency(&tree, new_id, &5));
+            assert!(has_dependency(&tree, new_id, &6));
+        }
+        assert_eq!(tree.tasks.get(&9).unwrap().get_repr(), "[O]     9: Part B");
+        assert!(tree.split_task(&tid4, vec!["Part C".to_string()]).is_err());
+        assert!(tree.split_task(&8, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_redundant_dependencies() {
+        let mut tree = setup_tree();
+        assert!(tree.find_redundant_dependencies().is_empty());
+
+        tree.add_dependency(&1, &4).unwrap();
+        tree.add_dependency(&1, &6).unwrap();
+        tree.add_dependency(&3, &6).unwrap();
+        assert_eq!(tree.find_redundant_dependencies(), vec![(1, 4), (1, 6)]);
+
+        assert_eq!(tree.remove_redundant_dependencies().unwrap(), vec![(1, 4), (1, 6)]);
+        assert!(tree.find_redundant_dependencies().is_empty());
+        assert!(tree.path_between(&1, &4));
+        assert!(tree.path_between(&1, &6));
+        assert!(has_dependency(&tree, &3, &6));
+    }
+
+    #[test]
+    fn test_resolve_task() {
+        let mut tree = setup_tree();
+        assert_eq!(tree.resolve_task("@last"), Ok(7));
+        let api_id = tree.add_task("Build the API".to_string(), None);
+        let ui_id = tree.add_task("Build UI".to_string(), None);
+        assert_eq!(tree.resolve_task("3"), Ok(3));
+        assert_eq!(tree.resolve_task("task 5"), Ok(5));
+        assert_eq!(tree.resolve_task("build-the-api"), Ok(api_id));
+        assert_eq!(tree.resolve_task("Build the"), Ok(api_id));
+        assert_eq!(tree.resolve_task("bui ui"), Ok(ui_id));
+        assert_eq!(tree.resolve_task("@last"), Ok(ui_id));
+        tree.set_status(&api_id, "in-progress".to_string()).unwrap();
+        assert_eq!(tree.resolve_task("@last"), Ok(api_id));
+
+        let ambiguous = tree.resolve_task("build").unwrap_err();
+        assert!(ambiguous.contains("Build the API") && ambiguous.contains("Build UI"));
+        assert!(tree.resolve_task("deploy").is_err());
+        tree.remove_task(&api_id).unwrap();
+        assert!(tree.resolve_task("@last").is_err());
+    }
 }
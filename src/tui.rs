@@ -0,0 +1,530 @@
+use std::collections::HashSet;
+use ratatui::crossterm::event::{ self, Event, KeyCode, KeyEvent, KeyEventKind };
+use ratatui::layout::{ Constraint, Layout };
+use ratatui::style::{ Color, Style, Stylize };
+use ratatui::text::{ Line, Span };
+use ratatui::widgets::{ Block, List, ListItem, ListState, Paragraph, Wrap };
+use ratatui::{ DefaultTerminal, Frame };
+use crate::project::Project;
+use crate::render::StatusFilter;
+use crate::session::Session;
+use crate::tree::{ ExternalRef, ReopenPolicy, TaskStatus, TaskTree, TID };
+
+
+const KEY_HELP: &str = "q quit  ↑↓ move  ←→ collapse/expand  s status  a add dep  \
+                        d remove dep  n new task  f filter  / search  esc clear";
+const COLLAPSED_MARKER: &str = "▸ ";
+const EXPANDED_MARKER: &str = "▾ ";
+const LEAF_MARKER: &str = "  ";
+const SHARED_MARKER: &str = " (*)";
+
+
+/// Run the full-screen terminal UI on the active project until the user quits. Changes are made
+/// through the same `TaskTree` and `Project` methods as the other actions, and saved right away.
+pub fn run(proj: Project, session: &mut Session) -> Result<String, String> {
+    let mut app = App::new(proj);
+    let mut terminal = ratatui::try_init()
+        .map_err(|err| format!("Could not start the terminal UI: {}", err))?;
+    let result = app.run(&mut terminal, session);
+    ratatui::restore();
+    result.map(|_| String::new())
+}
+
+
+/// A line of the dependency tree.
+#[derive(PartialEq, Debug)]
+struct Row {
+    task_id: TID,
+    depth: usize,
+    has_children: bool,
+    /// Whether the task's dependencies are drawn under an earlier row instead.
+    is_shared: bool,
+}
+
+/// What the user is typing in the prompt at the bottom of the screen.
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum Prompt {
+    Status,
+    AddDep,
+    RemoveDep,
+    NewTask,
+    Filter,
+    Search,
+}
+
+impl Prompt {
+
+    fn get_label(&self, tree: &TaskTree) -> String {
+        let status_names: Vec<&str> = tree.get_workflow().get_states().iter()
+            .map(|state| state.get_name())
+            .collect();
+        match self {
+            Self::Status => format!("new status ({})", status_names.join(", ")),
+            Self::AddDep => "depend on task (id, name or project:task_id)".to_string(),
+            Self::RemoveDep => {
+                "remove dependency on task (id, name or project:task_id)".to_string()
+            },
+            Self::NewTask => "new task name".to_string(),
+            Self::Filter => format!("show status ({}, available, all)", status_names.join(", ")),
+            Self::Search => "search".to_string(),
+        }
+    }
+
+}
+
+
+struct App {
+    proj: Project,
+    collapsed: HashSet<TID>,
+    status_flag: Option<String>,
+    query: Option<String>,
+    rows: Vec<Row>,
+    list_state: ListState,
+    prompt: Option<(Prompt, String)>,
+    /// The result of the last change, and whether it's an error.
+    message: Option<(String, bool)>,
+}
+
+impl App {
+
+    fn new(proj: Project) -> Self {
+        let mut app = App {
+            proj,
+            collapsed: HashSet::new(),
+            status_flag: None,
+            query: None,
+            rows: Vec::new(),
+            list_state: ListState::default(),
+            prompt: None,
+            message: None,
+        };
+        app.refresh_rows(None);
+        app
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal, session: &mut Session) -> Result<(), String> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))
+                .map_err(|err| format!("Could not draw the terminal UI: {}", err))?;
+            let event = event::read()
+                .map_err(|err| format!("Could not read from the terminal: {}", err))?;
+            if let Event::Key(key) = event {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key, session) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn get_selected(&self) -> Option<TID> {
+        self.list_state.selected().and_then(|idx| self.rows.get(idx)).map(|row| row.task_id)
+    }
+
+    /// Recompute the visible rows, keeping the given task (or else the same row) selected.
+    fn refresh_rows(&mut self, select: Option<TID>) {
+        let tree = self.proj.get_tree();
+        let filter = StatusFilter::from_opt_status_flag(tree, self.status_flag.clone())
+            .unwrap_or(StatusFilter::All);
+        self.rows = get_rows(tree, &self.collapsed, &filter, self.query.as_deref());
+        let selected = select.or_else(|| self.get_selected())
+            .and_then(|task_id| self.rows.iter().position(|row| row.task_id == task_id))
+            .or_else(|| self.list_state.selected())
+            .map(|idx| idx.min(self.rows.len().saturating_sub(1)));
+        self.list_state.select(if self.rows.is_empty() { None } else { selected.or(Some(0)) });
+    }
+
+    /// Handle a key press. Returns false if the user quit.
+    fn handle_key(&mut self, key: KeyEvent, session: &mut Session) -> bool {
+        if let Some((prompt, mut input)) = self.prompt.take() {
+            match key.code {
+                KeyCode::Enter => self.submit(prompt, input.trim(), session),
+                KeyCode::Esc => (),
+                KeyCode::Backspace => {
+                    input.pop();
+                    self.prompt = Some((prompt, input));
+                },
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    self.prompt = Some((prompt, input));
+                },
+                _ => self.prompt = Some((prompt, input)),
+            }
+            return true;
+        }
+
+        self.message = None;
+        let selected = self.get_selected();
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.list_state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.list_state.select_next(),
+            KeyCode::Home | KeyCode::Char('g') => self.list_state.select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.list_state.select_last(),
+            KeyCode::Right | KeyCode::Char('l') => {
+                if let Some(task_id) = selected {
+                    self.collapsed.remove(&task_id);
+                    self.refresh_rows(None);
+                }
+            },
+            KeyCode::Left | KeyCode::Char('h') => self.collapse_or_go_up(),
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                if let Some(task_id) = selected {
+                    if !self.collapsed.remove(&task_id) {
+                        self.collapsed.insert(task_id);
+                    }
+                    self.refresh_rows(None);
+                }
+            },
+            KeyCode::Char('s') if selected.is_some() => self.start_prompt(Prompt::Status),
+            KeyCode::Char('a') if selected.is_some() => self.start_prompt(Prompt::AddDep),
+            KeyCode::Char('d') if selected.is_some() => self.start_prompt(Prompt::RemoveDep),
+            KeyCode::Char('n') => self.start_prompt(Prompt::NewTask),
+            KeyCode::Char('f') => self.start_prompt(Prompt::Filter),
+            KeyCode::Char('/') => self.start_prompt(Prompt::Search),
+            KeyCode::Esc => {
+                self.status_flag = None;
+                self.query = None;
+                self.refresh_rows(None);
+            },
+            _ => (),
+        }
+        true
+    }
+
+    fn start_prompt(&mut self, prompt: Prompt) {
+        self.prompt = Some((prompt, String::new()));
+    }
+
+    /// Collapse the selected task, or if it's already collapsed (or has no dependencies), select
+    /// the task which depends on it.
+    fn collapse_or_go_up(&mut self) {
+        let idx = match self.list_state.selected() {
+            Some(idx) if idx < self.rows.len() => idx,
+            _ => return,
+        };
+        let row = &self.rows[idx];
+        if row.has_children && !row.is_shared && !self.collapsed.contains(&row.task_id) {
+            self.collapsed.insert(row.task_id);
+            self.refresh_rows(None);
+        } else {
+            let parent_idx = self.rows[..idx].iter().rposition(|other| other.depth < row.depth);
+            if parent_idx.is_some() {
+                self.list_state.select(parent_idx);
+            }
+        }
+    }
+
+    fn submit(&mut self, prompt: Prompt, input: &str, session: &mut Session) {
+        let selected = self.get_selected();
+        let result = match prompt {
+            Prompt::Filter => {
+                let status_flag = if input.is_empty() { None } else { Some(input.to_string()) };
+                StatusFilter::from_opt_status_flag(self.proj.get_tree(), status_flag.clone())
+                    .map(|_| {
+                        self.status_flag = status_flag;
+                        self.refresh_rows(None);
+                        None
+                    })
+            },
+            Prompt::Search => {
+                self.query = if input.is_empty() { None } else { Some(input.to_string()) };
+                self.refresh_rows(None);
+                Ok(None)
+            },
+            Prompt::NewTask if input.is_empty() => Err("The task needs a name.".to_string()),
+            Prompt::NewTask => self.update(session, |proj| {
+                let task_id = proj.get_tree_mut().add_task(input.to_string(), None);
+                Ok((task_id, format!("Created task {} with id {}.", input, task_id)))
+            }).map(|(task_id, msg)| {
+                self.refresh_rows(Some(task_id));
+                Some(msg)
+            }),
+            Prompt::Status => self.set_status(session, selected.unwrap(), input).map(Some),
+            Prompt::AddDep => self.update(session, |proj| {
+                let task_id = selected.unwrap();
+                let dependency = match ExternalRef::parse(input) {
+                    Some(external_ref) => {
+                        let dependency = external_ref.to_string();
+                        proj.add_external_dependency(&task_id, external_ref)?;
+                        dependency
+                    },
+                    None => {
+                        let dependency_id = proj.get_tree().resolve_task(input)?;
+                        proj.add_dependency(&task_id, &dependency_id)?;
+                        dependency_id.to_string()
+                    },
+                };
+                Ok(format!("Added task {} as a dependency for task {}.", dependency, task_id))
+            }).map(Some),
+            Prompt::RemoveDep => self.update(session, |proj| {
+                let task_id = selected.unwrap();
+                let tree = proj.get_tree_mut();
+                let dependency = match ExternalRef::parse(input) {
+                    Some(external_ref) => {
+                        tree.remove_external_dependency(&task_id, &external_ref)?;
+                        external_ref.to_string()
+                    },
+                    None => {
+                        let dependency_id = tree.resolve_task(input)?;
+                        tree.remove_dependency(&task_id, &dependency_id)?;
+                        dependency_id.to_string()
+                    },
+                };
+                Ok(format!("Removed dependency of task {} on task {}.", task_id, dependency))
+            }).map(Some),
+        };
+        self.message = match result {
+            Ok(Some(msg)) => Some((msg, false)),
+            Ok(None) => None,
+            Err(msg) => Some((strip_ansi(&msg), true)),
+        };
+    }
+
+    fn set_status(
+        &mut self,
+        session: &mut Session,
+        task_id: TID,
+        status: &str,
+    ) -> Result<String, String> {
+        let (closed_dependents, reopen_policy) = self.update(session, |proj| {
+            let tree = proj.get_tree_mut();
+            Ok((tree.set_status(&task_id, status.to_string())?, tree.get_reopen_policy()))
+        })?;
+        let mut msg = format!("Set task {}'s status to {}.", task_id, status);
+        if !closed_dependents.is_empty() {
+            let dependent_ids: Vec<String> = closed_dependents.iter()
+                .map(|task_id| task_id.to_string())
+                .collect();
+            match reopen_policy {
+                ReopenPolicy::Reopen => msg.push_str(&format!(
+                    " Also reopened tasks which depend on it: {}.",
+                    dependent_ids.join(", "),
+                )),
+                _ => msg.push_str(&format!(
+                    " Warning: closed tasks depend on it: {}.",
+                    dependent_ids.join(", "),
+                )),
+            }
+        }
+        Ok(msg)
+    }
+
+    /// Change a copy of the project and save it. The project is only replaced if both work, so a
+    /// rejected change leaves it as it was.
+    fn update<T>(
+        &mut self,
+        session: &mut Session,
+        change: impl FnOnce(&mut Project) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut proj = self.proj.clone();
+        let result = change(&mut proj)?;
+        session.save(&mut proj)?;
+        self.proj = proj;
+        self.refresh_rows(None);
+        Ok(result)
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main_area, bottom_area] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)])
+            .areas(frame.area());
+        let [tree_area, details_area] = Layout::horizontal([Constraint::Percentage(50); 2])
+            .areas(main_area);
+        let tree = self.proj.get_tree();
+
+        let mut title = format!(" {} ", self.proj.get_name());
+        if let Some(status_flag) = &self.status_flag {
+            title.push_str(&format!("[status: {}] ", status_flag));
+        }
+        if let Some(query) = &self.query {
+            title.push_str(&format!("[search: {}] ", query));
+        }
+        let items: Vec<ListItem> = self.rows.iter()
+            .map(|row| ListItem::new(self.format_row(row)))
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, tree_area, &mut self.list_state);
+
+        let details = match self.get_selected() {
+            Some(task_id) => strip_ansi(&tree.view_task(&task_id).unwrap()),
+            None => "No tasks to show.".to_string(),
+        };
+        let details = Paragraph::new(details)
+            .block(Block::bordered().title(" task "))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(details, details_area);
+
+        let bottom = match (&self.prompt, &self.message) {
+            (Some((prompt, input)), _) => {
+                Line::from(format!("{}: {}▏", prompt.get_label(tree), input))
+            },
+            (None, Some((msg, true))) => Line::from(msg.clone().red()),
+            (None, Some((msg, false))) => Line::from(msg.clone().green()),
+            (None, None) => Line::from(KEY_HELP.dark_gray()),
+        };
+        frame.render_widget(Paragraph::new(bottom), bottom_area);
+    }
+
+    fn format_row(&self, row: &Row) -> Line<'static> {
+        let task = self.proj.get_tree().get_task(&row.task_id).unwrap();
+        let marker = if !row.has_children || row.is_shared {
+            LEAF_MARKER
+        } else if self.collapsed.contains(&row.task_id) {
+            COLLAPSED_MARKER
+        } else {
+            EXPANDED_MARKER
+        };
+        let mut spans = vec![
+            Span::raw(format!("{}{}", "  ".repeat(row.depth), marker)),
+            Span::styled(task.get_status().to_string(), get_status_style(task.get_status())),
+            Span::raw(format!(" {}: {}", row.task_id, task.get_name())),
+        ];
+        if row.is_shared {
+            spans.push(Span::raw(SHARED_MARKER).dark_gray());
+        }
+        Line::from(spans)
+    }
+
+}
+
+
+/// Get the rows of the dependency tree, starting at the root tasks. Like `render::render_tree`,
+/// tasks reachable through more than one path are only expanded the first time. Tasks which don't
+/// match the filter and the search query are left out, unless one of their dependencies matches.
+fn get_rows(
+    tree: &TaskTree,
+    collapsed: &HashSet<TID>,
+    filter: &StatusFilter,
+    query: Option<&str>,
+) -> Vec<Row> {
+    let query = query.map(|query| query.to_lowercase());
+    let matches = |task_id: &TID| {
+        filter.matches(tree, task_id) && query.as_ref().is_none_or(|query| {
+            tree.get_task(task_id).unwrap().get_name().to_lowercase().contains(query)
+        })
+    };
+    let shown: HashSet<TID> = tree.get_task_ids().into_iter()
+        .filter(|task_id| matches(task_id))
+        .flat_map(|task_id| get_ancestor_ids(tree, task_id))
+        .collect();
+
+    let mut rows = Vec::new();
+    let mut expanded = HashSet::new();
+    let mut to_visit: Vec<(TID, usize)> = tree.get_root_ids().into_iter()
+        .rev()
+        .map(|root_id| (root_id, 0))
+        .collect();
+    while let Some((task_id, depth)) = to_visit.pop() {
+        if !shown.contains(&task_id) {
+            continue;
+        }
+        let mut children = tree.get_children(&task_id).unwrap().clone();
+        children.retain(|child_id| shown.contains(child_id));
+        children.sort();
+        let is_shared = !expanded.insert(task_id);
+        rows.push(Row { task_id, depth, has_children: !children.is_empty(), is_shared });
+        if !is_shared && !collapsed.contains(&task_id) {
+            to_visit.extend(children.into_iter().rev().map(|child_id| (child_id, depth + 1)));
+        }
+    }
+    rows
+}
+
+/// Get a task and the tasks which (transitively) depend on it.
+fn get_ancestor_ids(tree: &TaskTree, task_id: TID) -> HashSet<TID> {
+    let mut ancestor_ids = HashSet::new();
+    let mut to_visit = vec![task_id];
+    while let Some(visit_id) = to_visit.pop() {
+        if ancestor_ids.insert(visit_id) {
+            to_visit.extend(tree.get_parents(&visit_id).unwrap());
+        }
+    }
+    ancestor_ids
+}
+
+fn get_status_style(status: &TaskStatus) -> Style {
+    match status {
+        TaskStatus::Open => Style::new(),
+        TaskStatus::InProgress => Style::new().fg(Color::Yellow),
+        TaskStatus::Closed => Style::new().fg(Color::Green),
+        TaskStatus::Cancelled => Style::new().fg(Color::DarkGray),
+        TaskStatus::Custom(_) => Style::new().fg(Color::Cyan),
+    }
+}
+
+/// Remove the ANSI escape codes (used for bold text) from a message.
+fn strip_ansi(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for code_char in chars.by_ref() {
+                if code_char.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::tests::setup_tree;
+
+    fn get_row_ids(rows: &[Row]) -> Vec<(TID, usize)> {
+        rows.iter().map(|row| (row.task_id, row.depth)).collect()
+    }
+
+    #[test]
+    fn test_get_rows() {
+        let mut tree = TaskTree::new();
+        let root = tree.add_task("root".to_string(), None);
+        let left = tree.add_task("left".to_string(), None);
+        let right = tree.add_task("right".to_string(), None);
+        let shared = tree.add_task("shared".to_string(), None);
+        tree.add_dependency(&root, &left).unwrap();
+        tree.add_dependency(&root, &right).unwrap();
+        tree.add_dependency(&left, &shared).unwrap();
+        tree.add_dependency(&right, &shared).unwrap();
+        tree.set_status(&shared, "closed".to_string()).unwrap();
+
+        let rows = get_rows(&tree, &HashSet::new(), &StatusFilter::All, None);
+        assert_eq!(
+            get_row_ids(&rows),
+            vec![(root, 0), (left, 1), (shared, 2), (right, 1), (shared, 2)],
+        );
+        assert!(!rows[2].is_shared && rows[4].is_shared);
+
+        let rows = get_rows(&tree, &HashSet::from([left]), &StatusFilter::All, None);
+        assert_eq!(get_row_ids(&rows), vec![(root, 0), (left, 1), (right, 1), (shared, 2)]);
+
+        let closed = StatusFilter::Status(TaskStatus::Closed);
+        let rows = get_rows(&tree, &HashSet::new(), &closed, Some("RIGHT"));
+        assert!(rows.is_empty());
+        let rows = get_rows(&tree, &HashSet::new(), &closed, Some("SHA"));
+        assert_eq!(rows.len(), 5);
+        let rows = get_rows(&tree, &HashSet::new(), &StatusFilter::All, Some("left"));
+        assert_eq!(get_row_ids(&rows), vec![(root, 0), (left, 1)]);
+        assert!(!rows[1].has_children);
+    }
+
+    #[test]
+    fn test_get_rows_covers_every_task() {
+        let tree = setup_tree();
+        let rows = get_rows(&tree, &HashSet::new(), &StatusFilter::All, None);
+        let row_ids: HashSet<TID> = rows.iter().map(|row| row.task_id).collect();
+        assert_eq!(row_ids, tree.get_task_ids().into_iter().collect());
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let msg = "No such status \x1b[1mdone\x1b[0m";
+        assert_eq!(strip_ansi(msg), "No such status done");
+    }
+}
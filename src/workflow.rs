@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::tree::TaskStatus;
+use crate::command::bold_text;
+
+
+/// A struct representing a project's workflow: which statuses tasks can have, which of them count
+/// as done, and which status changes are allowed. The default workflow has the statuses open,
+/// in-progress, closed and cancelled, and allows any status change.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Workflow {
+    #[serde(deserialize_with = "deserialize_states")]
+    states: Vec<WorkflowState>,
+    /// Allowed status changes, from a status' name to the names of the statuses it can be changed
+    /// to. Statuses without an entry can be changed to any status.
+    transitions: HashMap<String, Vec<String>>,
+}
+
+/// A status in a workflow.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct WorkflowState {
+    name: String,
+    /// Whether tasks with this status count as done, i.e. no longer block the tasks depending on
+    /// them.
+    done: bool,
+    /// Whether a task can only have this status once all of its dependencies are done.
+    needs_deps_done: bool,
+}
+
+impl WorkflowState {
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    pub fn needs_deps_done(&self) -> bool {
+        self.needs_deps_done
+    }
+
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        Workflow {
+            states: vec![
+                WorkflowState { name: "open".to_string(), done: false, needs_deps_done: false },
+                WorkflowState {
+                    name: "in-progress".to_string(),
+                    done: false,
+                    needs_deps_done: true,
+                },
+                WorkflowState { name: "closed".to_string(), done: true, needs_deps_done: true },
+                cancelled_state(),
+            ],
+            transitions: HashMap::new(),
+        }
+    }
+}
+
+/// Cancelled tasks count as done, so they don't block the tasks depending on them, but can be
+/// cancelled regardless of their own dependencies.
+fn cancelled_state() -> WorkflowState {
+    WorkflowState { name: "cancelled".to_string(), done: true, needs_deps_done: false }
+}
+
+/// Deserialize a workflow's statuses, adding the cancelled status to workflows saved before it
+/// existed. A custom cancelled status defined before then is replaced by the built-in one.
+fn deserialize_states<'de, D>(deserializer: D) -> Result<Vec<WorkflowState>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut states = Vec::<WorkflowState>::deserialize(deserializer)?;
+    match states.iter().position(|state| state.name == "cancelled") {
+        Some(idx) => states[idx] = cancelled_state(),
+        None => {
+            let closed_idx = states.iter().position(|state| state.name == "closed");
+            let idx = closed_idx.map_or(states.len(), |idx| idx + 1);
+            states.insert(idx, cancelled_state());
+        },
+    }
+    Ok(states)
+}
+
+impl Workflow {
+
+    /// Parse a status flag, requiring the status to be part of this workflow.
+    pub fn parse_status(&self, status_flag: &str) -> Result<TaskStatus, String> {
+        match self.get_state(status_flag) {
+            Some(_) => Ok(TaskStatus::from_name(status_flag)),
+            None => Err(format!("No such status {}", bold_text(status_flag))),
+        }
+    }
+
+    /// Parse an optional status flag, requiring the status to be part of this workflow.
+    pub fn parse_opt_status(
+        &self,
+        opt_status_flag: Option<String>,
+    ) -> Result<Option<TaskStatus>, String> {
+        match opt_status_flag {
+            Some(status_flag) => Ok(Some(self.parse_status(&status_flag)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get this workflow's statuses, in the order they were defined.
+    pub fn get_states(&self) -> &[WorkflowState] {
+        &self.states
+    }
+
+    /// Get the statuses which a task with the given status can be changed to, if restricted.
+    pub fn get_transitions(&self, status_name: &str) -> Option<&Vec<String>> {
+        self.transitions.get(status_name)
+    }
+
+    /// Check whether tasks with the given status count as done. Statuses which aren't part of
+    /// this workflow (e.g. those of tasks in other projects) count as done only if they're closed
+    /// or cancelled.
+    pub fn is_done(&self, status: &TaskStatus) -> bool {
+        match self.get_state(status.to_name()) {
+            Some(state) => state.done,
+            None => *status == TaskStatus::Closed || *status == TaskStatus::Cancelled,
+        }
+    }
+
+    /// Check whether a task can only have the given status once all of its dependencies are done.
+    pub fn needs_deps_done(&self, status: &TaskStatus) -> bool {
+        match self.get_state(status.to_name()) {
+            Some(state) => state.needs_deps_done,
+            None => *status != TaskStatus::Open && *status != TaskStatus::Cancelled,
+        }
+    }
+
+    /// Check whether a task's status can be changed from `from` to `to`.
+    pub fn check_transition(&self, from: &TaskStatus, to: &TaskStatus) -> Result<(), String> {
+        if from == to {
+            return Ok(());
+        }
+        match self.transitions.get(from.to_name()) {
+            Some(allowed) if !allowed.iter().any(|name| name == to.to_name()) => Err(format!(
+                "Cannot change status from {} to {}; allowed: {}",
+                bold_text(from.to_name()),
+                bold_text(to.to_name()),
+                if allowed.is_empty() { "none".to_string() } else { allowed.join(", ") },
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Add a status to this workflow. Its marker (e.g. "[IR]" for in-review) must differ from the
+    /// markers of the other statuses, so that the marker identifies the status.
+    pub fn add_state(
+        &mut self,
+        name: &str,
+        done: bool,
+        needs_deps_done: bool,
+    ) -> Result<(), String> {
+        if self.get_state(name).is_some() {
+            return Err(format!("Status {} already exists.", bold_text(name)));
+        }
+        let valid_name = !name.is_empty()
+            && name != "all"
+            && name != "available"
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !valid_name {
+            return Err(format!(
+                "Invalid status name {}; use lowercase letters, digits and dashes.",
+                bold_text(name),
+            ));
+        }
+        let marker = TaskStatus::from_name(name).to_string();
+        let same_marker = self.states.iter()
+            .find(|state| TaskStatus::from_name(&state.name).to_string() == marker);
+        if let Some(state) = same_marker {
+            return Err(format!(
+                "Status {} would be shown as {}, like status {}; use another name.",
+                bold_text(name),
+                marker,
+                bold_text(&state.name),
+            ));
+        }
+        self.states.push(WorkflowState { name: name.to_string(), done, needs_deps_done });
+        Ok(())
+    }
+
+    /// Remove a status from this workflow, along with any transitions to or from it. The caller is
+    /// responsible for checking that no task has this status. The built-in statuses can't be
+    /// removed.
+    pub fn remove_state(&mut self, name: &str) -> Result<(), String> {
+        if TaskStatus::from_status_flag(name).is_ok() {
+            return Err(format!("The {} status cannot be removed.", bold_text(name)));
+        }
+        if self.get_state(name).is_none() {
+            return Err(format!("No such status {}", bold_text(name)));
+        }
+        self.states.retain(|state| state.name != name);
+        self.transitions.remove(name);
+        for allowed in self.transitions.values_mut() {
+            allowed.retain(|to| to != name);
+        }
+        Ok(())
+    }
+
+    /// Set the statuses which a task with the status `from` can be changed to. If `to` is None,
+    /// allows changing to any status.
+    pub fn set_transitions(&mut self, from: &str, to: Option<Vec<String>>) -> Result<(), String> {
+        self.parse_status(from)?;
+        match to {
+            None => {
+                self.transitions.remove(from);
+            },
+            Some(to) => {
+                for name in &to {
+                    self.parse_status(name)?;
+                }
+                self.transitions.insert(from.to_string(), to);
+            },
+        }
+        Ok(())
+    }
+
+    fn get_state(&self, name: &str) -> Option<&WorkflowState> {
+        self.states.iter().find(|state| state.name == name)
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_workflow() {
+        let workflow = Workflow::default();
+        assert_eq!(workflow.parse_status("in-progress").unwrap(), TaskStatus::InProgress);
+        assert!(workflow.parse_status("blocked").is_err());
+        assert!(workflow.is_done(&TaskStatus::Closed));
+        assert!(!workflow.is_done(&TaskStatus::InProgress));
+        assert!(workflow.needs_deps_done(&TaskStatus::InProgress));
+        assert!(!workflow.needs_deps_done(&TaskStatus::Open));
+        assert!(workflow.is_done(&TaskStatus::Cancelled));
+        assert!(!workflow.needs_deps_done(&TaskStatus::Cancelled));
+        assert!(workflow.check_transition(&TaskStatus::Closed, &TaskStatus::Open).is_ok());
+    }
+
+    #[test]
+    fn test_custom_states_and_transitions() {
+        let mut workflow = Workflow::default();
+        workflow.add_state("blocked", false, false).unwrap();
+        workflow.add_state("wont-fix", true, false).unwrap();
+        assert!(workflow.add_state("blocked", false, false).is_err());
+        assert!(workflow.add_state("In Review", false, false).is_err());
+        assert!(workflow.add_state("all", false, false).is_err());
+        assert!(workflow.add_state("cool", false, false).is_err());
+        assert!(workflow.add_state("x", true, false).is_err());
+        assert!(workflow.add_state("will-fix", false, false).is_err());
+
+        let blocked = workflow.parse_status("blocked").unwrap();
+        assert_eq!(blocked, TaskStatus::Custom("blocked".to_string()));
+        assert!(workflow.is_done(&TaskStatus::Custom("wont-fix".to_string())));
+        assert!(!workflow.is_done(&blocked));
+
+        workflow.set_transitions("open", Some(vec!["in-progress".to_string()])).unwrap();
+        assert!(workflow.check_transition(&TaskStatus::Open, &blocked).is_err());
+        assert!(workflow.check_transition(&TaskStatus::Open, &TaskStatus::InProgress).is_ok());
+        assert!(workflow.set_transitions("open", Some(vec!["bogus".to_string()])).is_err());
+        workflow.set_transitions("open", None).unwrap();
+        assert!(workflow.check_transition(&TaskStatus::Open, &blocked).is_ok());
+
+        workflow.set_transitions("blocked", Some(vec!["open".to_string()])).unwrap();
+        workflow.remove_state("blocked").unwrap();
+        assert!(workflow.parse_status("blocked").is_err());
+        assert!(workflow.get_transitions("blocked").is_none());
+        assert!(workflow.remove_state("closed").is_err());
+        assert!(workflow.remove_state("in-progress").is_err());
+    }
+
+    #[test]
+    fn test_old_workflow_gets_cancelled_state() {
+        let mut workflow = Workflow::default();
+        workflow.states.retain(|state| state.name != "cancelled");
+        workflow.add_state("blocked", false, false).unwrap();
+        let serialized = serde_json::to_string(&workflow).unwrap();
+        let deserialized: Workflow = serde_json::from_str(&serialized).unwrap();
+        let names: Vec<&str> = deserialized.get_states().iter()
+            .map(|state| state.get_name())
+            .collect();
+        assert_eq!(names, vec!["open", "in-progress", "closed", "cancelled", "blocked"]);
+    }
+}